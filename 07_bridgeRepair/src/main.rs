@@ -1,3 +1,6 @@
+mod operators;
+
+use operators::{parse_operators, Operator};
 use std::env;
 use std::fs;
 use std::time::Instant;
@@ -8,10 +11,12 @@ struct Equation {
     right: Vec<u64>,
 }
 
-enum Op {
-    Concat,
-    Mul,
-    Add,
+#[derive(Clone, Copy)]
+enum Semantics {
+    // Every operator is applied as soon as it is read
+    LeftToRight,
+    // Operators with higher precedence are evaluated first (e.g. `*` before `+`)
+    Precedence,
 }
 
 fn main() {
@@ -19,12 +24,33 @@ fn main() {
     let input_file = args.get(1).expect("Input file is required");
     let input = parse_input(input_file);
 
-    let now = Instant::now();
-    let result = if args.contains(&"--part-2".to_string()) {
-        part_2(input)
-    } else {
-        part_1(input)
+    let custom_ops = args
+        .iter()
+        .position(|a| a == "--ops")
+        .map(|i| args.get(i + 1).expect("Missing value for --ops").as_str());
+    let ops_spec = match custom_ops {
+        Some(spec) => spec,
+        None if args.contains(&"--part-2".to_string()) => "concat,mul,add",
+        None => "mul,add",
+    };
+    let ops = parse_operators(ops_spec);
+    // Only echoed back for --ops, so that the parts print just their answer
+    if custom_ops.is_some() {
+        let op_names: Vec<String> = ops.iter().map(|op| op.name()).collect();
+        println!("Operators: {}", op_names.join(", "));
+    }
+
+    let semantics = match args.iter().position(|a| a == "--semantics") {
+        Some(i) => match args.get(i + 1).map(|s| s.as_str()) {
+            Some("left-to-right") => Semantics::LeftToRight,
+            Some("precedence") => Semantics::Precedence,
+            other => panic!("Unknown semantics: {:?}", other),
+        },
+        None => Semantics::LeftToRight,
     };
+
+    let now = Instant::now();
+    let result = solve(input, &ops, semantics);
     let elapsed = now.elapsed();
     println!("{:?}", result);
     println!("Executed in {:?}", elapsed);
@@ -37,7 +63,7 @@ fn parse_input(input_file: &str) -> Vec<Equation> {
         .map(|l| {
             let split: Vec<&str> = l.split(": ").collect();
             let result = split
-                .get(0)
+                .first()
                 .expect("Failed to get equation result")
                 .parse()
                 .expect("Failed to parse equation result");
@@ -52,50 +78,272 @@ fn parse_input(input_file: &str) -> Vec<Equation> {
         .collect()
 }
 
-fn part_1(input: Vec<Equation>) -> u64 {
+fn solve(input: Vec<Equation>, ops: &[Box<dyn Operator>], semantics: Semantics) -> u64 {
+    let monotonic = ops.iter().all(|op| op.is_monotonic());
     input
         .iter()
-        .filter(|e| is_solvable(e.result, e.right[0], &e.right[1..], false))
+        .filter(|e| is_equation_solvable(e, ops, semantics, monotonic))
         .map(|e| e.result)
         .sum()
 }
 
-fn part_2(input: Vec<Equation>) -> u64 {
-    input
-        .iter()
-        .filter(|e| is_solvable(e.result, e.right[0], &e.right[1..], true))
-        .map(|e| e.result)
-        .sum()
+fn is_equation_solvable(
+    e: &Equation,
+    ops: &[Box<dyn Operator>],
+    semantics: Semantics,
+    monotonic: bool,
+) -> bool {
+    // Pruning is only safe if no operator can bring the value back down, which
+    // monotonic operators can still do with a 0
+    let prune = monotonic && e.right.iter().all(|&n| n >= 1);
+    match semantics {
+        Semantics::LeftToRight => is_solvable(e.result, e.right[0], &e.right[1..], ops, prune),
+        Semantics::Precedence => {
+            is_solvable_precedence(e.result, &[], e.right[0], &e.right[1..], ops, prune)
+        }
+    }
 }
 
-fn is_solvable(target: u64, current: u64, numbers: &[u64], try_concat: bool) -> bool {
-    if current > target {
+fn is_solvable(
+    target: u64,
+    current: u64,
+    numbers: &[u64],
+    ops: &[Box<dyn Operator>],
+    prune: bool,
+) -> bool {
+    if prune && current > target {
         return false;
     }
 
-    if numbers.len() == 0 {
+    if numbers.is_empty() {
         return current == target;
     }
 
-    let n = numbers.get(0).expect("Failed to get number");
+    let n = numbers.first().expect("Failed to get number");
 
-    let ops: Vec<Op> = if try_concat {
-        vec![Op::Concat, Op::Mul, Op::Add]
-    } else {
-        vec![Op::Mul, Op::Add]
-    };
+    for op in ops {
+        let new_curr = match op.apply(current, *n) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        if is_solvable(target, new_curr, &numbers[1..], ops, prune) {
+            return true;
+        }
+    }
+
+    false
+}
+
+// `pending` holds the operations that are still waiting for their right operand to be
+// fully evaluated, with increasing precedence (equal only for right-associative
+// operators, like `2 ^ 3 ^ 2` = `2 ^ (3 ^ 2)`). With only `+` and `*` this is
+// a running sum plus the product term currently being built.
+fn is_solvable_precedence(
+    target: u64,
+    pending: &[(u64, &dyn Operator)],
+    current: u64,
+    numbers: &[u64],
+    ops: &[Box<dyn Operator>],
+    prune: bool,
+) -> bool {
+    // With monotonic operators, whatever comes next can only make the total bigger
+    if prune || numbers.is_empty() {
+        let total = match reduce(pending, current, None) {
+            Some((_, total)) => total,
+            None => return false,
+        };
+        if numbers.is_empty() {
+            return total == target;
+        }
+        if total > target {
+            return false;
+        }
+    }
+
+    let n = numbers.first().expect("Failed to get number");
 
     for op in ops {
-        let new_curr = match op {
-            Op::Concat => (current * 10_u64.pow(n.to_string().len() as u32)) + n,
-            Op::Mul => current * n,
-            Op::Add => current + n,
+        let (kept, left) = match reduce(pending, current, Some(op.as_ref())) {
+            Some(reduced) => reduced,
+            None => continue,
         };
 
-        if new_curr <= target && is_solvable(target, new_curr, &numbers[1..], try_concat) {
+        let mut new_pending = pending[..kept].to_vec();
+        new_pending.push((left, op.as_ref()));
+
+        if is_solvable_precedence(target, &new_pending, *n, &numbers[1..], ops, prune) {
             return true;
         }
     }
 
     false
 }
+
+// Applies the pending operations that take `current` before `next` can (all of
+// them at the end of the equation). Returns how many of them are still pending
+// and the resulting operand.
+fn reduce(
+    pending: &[(u64, &dyn Operator)],
+    current: u64,
+    next: Option<&dyn Operator>,
+) -> Option<(usize, u64)> {
+    let binds_first = |op: &dyn Operator| match next {
+        Some(next) if next.is_right_associative() => op.precedence() > next.precedence(),
+        Some(next) => op.precedence() >= next.precedence(),
+        None => true,
+    };
+    let mut current = current;
+    let mut kept = pending.len();
+    while kept > 0 && binds_first(pending[kept - 1].1) {
+        let (left, op) = pending[kept - 1];
+        current = op.apply(left, current)?;
+        kept -= 1;
+    }
+    Some((kept, current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use proptest::sample::subsequence;
+
+    const OP_NAMES: [&str; 8] = [
+        "add", "sub", "mul", "div", "pow", "xor", "concat", "concat2",
+    ];
+
+    // Every value the numbers can make, trying every sequence of operators
+    fn brute_force_left_to_right(numbers: &[u64], ops: &[Box<dyn Operator>]) -> Vec<u64> {
+        numbers[1..].iter().fold(vec![numbers[0]], |values, &n| {
+            values
+                .iter()
+                .flat_map(|&v| ops.iter().filter_map(move |op| op.apply(v, n)))
+                .collect()
+        })
+    }
+
+    fn check(
+        numbers: &[u64],
+        ops: &[Box<dyn Operator>],
+        semantics: Semantics,
+        reachable: &[u64],
+        other_target: u64,
+    ) -> Result<(), TestCaseError> {
+        let monotonic = ops.iter().all(|op| op.is_monotonic());
+        for &result in reachable.iter().chain([&other_target]) {
+            let equation = Equation {
+                result,
+                right: numbers.to_vec(),
+            };
+            prop_assert_eq!(
+                is_equation_solvable(&equation, ops, semantics, monotonic),
+                reachable.contains(&result),
+                "{:?}",
+                equation
+            );
+        }
+        Ok(())
+    }
+
+    // Value of numbers[0] ops[0] numbers[1] ..., splitting at the operator that
+    // binds loosest: the last one of them, or the first if it's right-associative
+    fn evaluate(numbers: &[u64], ops: &[&dyn Operator]) -> Option<u64> {
+        let Some(loosest) = ops.iter().map(|op| op.precedence()).min() else {
+            return Some(numbers[0]);
+        };
+        let mut split = ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| op.precedence() == loosest);
+        let (k, op) = if ops
+            .iter()
+            .any(|op| op.precedence() == loosest && op.is_right_associative())
+        {
+            split.next()
+        } else {
+            split.next_back()
+        }?;
+        let left = evaluate(&numbers[..=k], &ops[..k])?;
+        let right = evaluate(&numbers[k + 1..], &ops[k + 1..])?;
+        op.apply(left, right)
+    }
+
+    // Every value the numbers can make with standard precedence
+    fn brute_force_precedence(numbers: &[u64], ops: &[Box<dyn Operator>]) -> Vec<u64> {
+        let mut sequences: Vec<Vec<&dyn Operator>> = vec![vec![]];
+        for _ in 1..numbers.len() {
+            sequences = sequences
+                .into_iter()
+                .flat_map(|seq| {
+                    ops.iter().map(move |op| {
+                        let mut seq = seq.clone();
+                        seq.push(op.as_ref());
+                        seq
+                    })
+                })
+                .collect();
+        }
+        sequences
+            .iter()
+            .filter_map(|seq| evaluate(numbers, seq))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn precedence_matches_brute_force(
+            numbers in prop::collection::vec(0u64..12, 1..6),
+            names in subsequence(OP_NAMES.to_vec(), 1..4),
+            other_target in 0u64..200,
+        ) {
+            let ops = parse_operators(&names.join(","));
+            let reachable = brute_force_precedence(&numbers, &ops);
+            check(&numbers, &ops, Semantics::Precedence, &reachable, other_target)?;
+        }
+
+        #[test]
+        fn left_to_right_matches_brute_force(
+            numbers in prop::collection::vec(0u64..12, 1..6),
+            names in subsequence(OP_NAMES.to_vec(), 1..4),
+            other_target in 0u64..200,
+        ) {
+            let ops = parse_operators(&names.join(","));
+            let reachable = brute_force_left_to_right(&numbers, &ops);
+            check(&numbers, &ops, Semantics::LeftToRight, &reachable, other_target)?;
+        }
+    }
+
+    #[test]
+    fn pow_is_right_associative() {
+        let ops = parse_operators("pow");
+        let solvable = |result| {
+            let equation = Equation {
+                result,
+                right: vec![2, 3, 2],
+            };
+            is_equation_solvable(&equation, &ops, Semantics::Precedence, true)
+        };
+        assert!(solvable(512));
+        assert!(!solvable(64));
+    }
+
+    #[test]
+    fn zero_operands_dont_prune() {
+        let equation = Equation {
+            result: 1,
+            right: vec![5, 2, 0],
+        };
+        for spec in ["pow,add", "pow,add,sub", "mul,add"] {
+            let ops = parse_operators(spec);
+            let monotonic = ops.iter().all(|op| op.is_monotonic());
+            let expected = spec != "mul,add";
+            assert_eq!(
+                is_equation_solvable(&equation, &ops, Semantics::LeftToRight, monotonic),
+                expected,
+                "{}",
+                spec
+            );
+        }
+    }
+}
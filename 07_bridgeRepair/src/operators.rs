@@ -0,0 +1,199 @@
+pub trait Operator {
+    fn name(&self) -> String;
+
+    // Returns None when the result is undefined (overflow, negative, division by zero...)
+    fn apply(&self, current: u64, n: u64) -> Option<u64>;
+
+    // A monotonic operator never produces a value smaller than `current` when both
+    // operands are at least 1 (`x * 0` or `x ^ 0` can go down), so once
+    // `current > target` there is no point in continuing the search.
+    fn is_monotonic(&self) -> bool;
+
+    // Only used with standard precedence: higher binds tighter
+    fn precedence(&self) -> u8;
+
+    // Only used with standard precedence: whether `a op b op c` is `a op (b op c)`.
+    // Left to right, every operator is applied as soon as it is read.
+    fn is_right_associative(&self) -> bool {
+        false
+    }
+}
+
+struct Add;
+struct Sub;
+struct Mul;
+struct Div;
+struct Pow;
+struct Xor;
+struct Concat {
+    base: u64,
+}
+
+impl Operator for Add {
+    fn name(&self) -> String {
+        "add".to_string()
+    }
+
+    fn apply(&self, current: u64, n: u64) -> Option<u64> {
+        current.checked_add(n)
+    }
+
+    fn is_monotonic(&self) -> bool {
+        true
+    }
+
+    fn precedence(&self) -> u8 {
+        1
+    }
+}
+
+impl Operator for Sub {
+    fn name(&self) -> String {
+        "sub".to_string()
+    }
+
+    fn apply(&self, current: u64, n: u64) -> Option<u64> {
+        current.checked_sub(n)
+    }
+
+    fn is_monotonic(&self) -> bool {
+        false
+    }
+
+    fn precedence(&self) -> u8 {
+        1
+    }
+}
+
+impl Operator for Mul {
+    fn name(&self) -> String {
+        "mul".to_string()
+    }
+
+    fn apply(&self, current: u64, n: u64) -> Option<u64> {
+        current.checked_mul(n)
+    }
+
+    fn is_monotonic(&self) -> bool {
+        true
+    }
+
+    fn precedence(&self) -> u8 {
+        2
+    }
+}
+
+impl Operator for Div {
+    fn name(&self) -> String {
+        "div".to_string()
+    }
+
+    fn apply(&self, current: u64, n: u64) -> Option<u64> {
+        current.checked_div(n)
+    }
+
+    fn is_monotonic(&self) -> bool {
+        false
+    }
+
+    fn precedence(&self) -> u8 {
+        2
+    }
+}
+
+impl Operator for Pow {
+    fn name(&self) -> String {
+        "pow".to_string()
+    }
+
+    fn apply(&self, current: u64, n: u64) -> Option<u64> {
+        current.checked_pow(u32::try_from(n).ok()?)
+    }
+
+    fn is_monotonic(&self) -> bool {
+        true
+    }
+
+    fn precedence(&self) -> u8 {
+        3
+    }
+
+    // Like in maths, `2 ^ 3 ^ 2` is `2 ^ 9`
+    fn is_right_associative(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Xor {
+    fn name(&self) -> String {
+        "xor".to_string()
+    }
+
+    fn apply(&self, current: u64, n: u64) -> Option<u64> {
+        Some(current ^ n)
+    }
+
+    fn is_monotonic(&self) -> bool {
+        false
+    }
+
+    fn precedence(&self) -> u8 {
+        0
+    }
+}
+
+impl Operator for Concat {
+    fn name(&self) -> String {
+        if self.base == 10 {
+            "concat".to_string()
+        } else {
+            format!("concat{}", self.base)
+        }
+    }
+
+    fn apply(&self, current: u64, n: u64) -> Option<u64> {
+        // Shift `current` left by as many digits as `n` has in this base
+        let mut shift: u64 = self.base;
+        while shift <= n {
+            shift = shift.checked_mul(self.base)?;
+        }
+        current.checked_mul(shift)?.checked_add(n)
+    }
+
+    fn is_monotonic(&self) -> bool {
+        true
+    }
+
+    fn precedence(&self) -> u8 {
+        4
+    }
+}
+
+// Accepts "add", "sub", "mul", "div", "pow", "xor", "concat" (base 10)
+// and "concat<base>" (e.g. "concat2", "concat16")
+pub fn get_operator(name: &str) -> Option<Box<dyn Operator>> {
+    match name {
+        "add" => Some(Box::new(Add)),
+        "sub" => Some(Box::new(Sub)),
+        "mul" => Some(Box::new(Mul)),
+        "div" => Some(Box::new(Div)),
+        "pow" => Some(Box::new(Pow)),
+        "xor" => Some(Box::new(Xor)),
+        "concat" => Some(Box::new(Concat { base: 10 })),
+        _ => {
+            let base: u64 = name.strip_prefix("concat")?.parse().ok()?;
+            if base < 2 {
+                return None;
+            }
+            Some(Box::new(Concat { base }))
+        }
+    }
+}
+
+pub fn parse_operators(spec: &str) -> Vec<Box<dyn Operator>> {
+    spec.split(',')
+        .map(|name| {
+            get_operator(name.trim()).unwrap_or_else(|| panic!("Unknown operator: {}", name))
+        })
+        .collect()
+}
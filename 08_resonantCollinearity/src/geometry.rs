@@ -0,0 +1,252 @@
+// A point in an N-dimensional integer grid. For a single map N is 2 (row, column),
+// stacked floor plans add the layer as the first axis.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position<const N: usize>(pub [i32; N]);
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Delta<const N: usize>(pub [i32; N]);
+
+impl<const N: usize> std::ops::Add<Delta<N>> for Position<N> {
+    type Output = Position<N>;
+
+    fn add(self, rhs: Delta<N>) -> Self::Output {
+        Position(std::array::from_fn(|k| self.0[k] + rhs.0[k]))
+    }
+}
+
+impl<const N: usize> std::ops::Sub<Delta<N>> for Position<N> {
+    type Output = Position<N>;
+
+    fn sub(self, rhs: Delta<N>) -> Self::Output {
+        self + (-rhs)
+    }
+}
+
+impl<const N: usize> std::ops::Sub<Position<N>> for Position<N> {
+    type Output = Delta<N>;
+
+    fn sub(self, rhs: Position<N>) -> Self::Output {
+        Delta(std::array::from_fn(|k| self.0[k] - rhs.0[k]))
+    }
+}
+
+impl<const N: usize> std::ops::Neg for Delta<N> {
+    type Output = Delta<N>;
+
+    fn neg(self) -> Self::Output {
+        Delta(self.0.map(|d| -d))
+    }
+}
+
+impl<const N: usize> std::ops::Div<i32> for Delta<N> {
+    type Output = Delta<N>;
+
+    fn div(self, rhs: i32) -> Self::Output {
+        Delta(self.0.map(|d| d / rhs))
+    }
+}
+
+impl<const N: usize> Delta<N> {
+    // Smallest step in the same direction that still lands on grid points.
+    // (6, 4) and (3, 2) both reduce to (3, 2), so no point on the line is skipped.
+    pub fn reduced(self) -> Delta<N> {
+        let g = self.0.iter().fold(0, |g, &d| gcd(g, d));
+        if g == 0 {
+            self
+        } else {
+            self / g
+        }
+    }
+}
+
+// Size of the grid along each axis. Valid coordinates go from 0 to size - 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds<const N: usize>(pub [i32; N]);
+
+impl<const N: usize> Bounds<N> {
+    pub fn contains(&self, p: Position<N>) -> bool {
+        p.0.iter()
+            .zip(self.0.iter())
+            .all(|(&c, &size)| c >= 0 && c < size)
+    }
+}
+
+pub fn gcd(a: i32, b: i32) -> i32 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// Every grid point in line with p1 and p2 for which `in_bounds` holds
+pub fn line_points<const N: usize>(
+    p1: Position<N>,
+    p2: Position<N>,
+    in_bounds: impl Fn(Position<N>) -> bool,
+) -> Vec<Position<N>> {
+    let step = (p2 - p1).reduced();
+    let mut points = vec![];
+
+    let mut p = p1;
+    while in_bounds(p) {
+        points.push(p);
+        p = p + step;
+    }
+
+    p = p1 - step;
+    while in_bounds(p) {
+        points.push(p);
+        p = p - step;
+    }
+
+    points
+}
+
+// Grid points in line with p1 and p2 whose distance to p1 is `far / near` times their
+// distance to p2. Writing such a point as p1 + t * (p2 - p1), there is one solution
+// between the antennas (t = far / (far + near)) and one outside (t = far / (far - near)).
+// A solution is only kept if it has integer coordinates.
+pub fn ratio_points<const N: usize>(
+    p1: Position<N>,
+    p2: Position<N>,
+    far: i32,
+    near: i32,
+) -> Vec<Position<N>> {
+    let delta = p2 - p1;
+    let mut points = vec![];
+
+    if let Some(p) = fraction_point(p1, delta, far, far + near) {
+        points.push(p);
+    }
+    if far != near {
+        if let Some(p) = fraction_point(p1, delta, far, far - near) {
+            points.push(p);
+        }
+    }
+
+    points
+}
+
+// p + delta * num / den, if that is a grid point
+fn fraction_point<const N: usize>(
+    p: Position<N>,
+    delta: Delta<N>,
+    num: i32,
+    den: i32,
+) -> Option<Position<N>> {
+    let scaled = delta.0.map(|d| d * num);
+    if scaled.iter().any(|d| d % den != 0) {
+        return None;
+    }
+    Some(p + Delta(scaled.map(|d| d / den)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    // Two different antennas somewhere in a grid of up to `max_size` cells a side
+    fn antennas<const N: usize>(
+        max_size: i32,
+    ) -> impl Strategy<Value = (Bounds<N>, Position<N>, Position<N>)> {
+        proptest::array::uniform::<_, N>(1..=max_size)
+            .prop_flat_map(|sizes| {
+                let position = || sizes.map(|size| 0..size);
+                (Just(Bounds(sizes)), position(), position())
+            })
+            .prop_map(|(bounds, p1, p2)| (bounds, Position(p1), Position(p2)))
+            .prop_filter("antennas must differ", |(_, p1, p2)| p1 != p2)
+    }
+
+    fn grid<const N: usize>(bounds: Bounds<N>) -> Vec<Position<N>> {
+        let mut points = vec![Position([0; N])];
+        for axis in 0..N {
+            points = points
+                .into_iter()
+                .flat_map(|p| {
+                    (0..bounds.0[axis]).map(move |c| {
+                        let mut p = p;
+                        p.0[axis] = c;
+                        p
+                    })
+                })
+                .collect();
+        }
+        points
+    }
+
+    // Collinear if every 2x2 minor of the two difference vectors is zero
+    fn is_collinear<const N: usize>(p: Position<N>, p1: Position<N>, p2: Position<N>) -> bool {
+        let a = p2 - p1;
+        let b = p - p1;
+        (0..N).all(|x| (0..N).all(|y| a.0[x] * b.0[y] == a.0[y] * b.0[x]))
+    }
+
+    fn dist2<const N: usize>(a: Position<N>, b: Position<N>) -> i32 {
+        (a - b).0.iter().map(|d| d * d).sum()
+    }
+
+    fn check_line_points<const N: usize>(
+        (bounds, p1, p2): (Bounds<N>, Position<N>, Position<N>),
+    ) -> Result<(), TestCaseError> {
+        let found: HashSet<Position<N>> = line_points(p1, p2, |p| bounds.contains(p))
+            .into_iter()
+            .collect();
+        let expected: HashSet<Position<N>> = grid(bounds)
+            .into_iter()
+            .filter(|&p| is_collinear(p, p1, p2))
+            .collect();
+        prop_assert_eq!(found, expected);
+        Ok(())
+    }
+
+    fn check_ratio_points<const N: usize>(
+        (bounds, p1, p2): (Bounds<N>, Position<N>, Position<N>),
+        (far, near): (i32, i32),
+    ) -> Result<(), TestCaseError> {
+        let found: HashSet<Position<N>> = ratio_points(p1, p2, far, near)
+            .into_iter()
+            .filter(|&p| bounds.contains(p))
+            .collect();
+        let expected: HashSet<Position<N>> = grid(bounds)
+            .into_iter()
+            .filter(|&p| is_collinear(p, p1, p2))
+            .filter(|&p| dist2(p, p1) * near * near == dist2(p, p2) * far * far)
+            .collect();
+        prop_assert_eq!(found, expected);
+        Ok(())
+    }
+
+    const RATIOS: [(i32, i32); 5] = [(2, 1), (1, 2), (3, 1), (3, 2), (1, 1)];
+
+    proptest! {
+        #[test]
+        fn line_points_match_brute_force_2d(case in antennas::<2>(15)) {
+            check_line_points(case)?;
+        }
+
+        #[test]
+        fn line_points_match_brute_force_3d(case in antennas::<3>(7)) {
+            check_line_points(case)?;
+        }
+
+        #[test]
+        fn ratio_points_match_brute_force_2d(
+            case in antennas::<2>(15),
+            ratio in proptest::sample::select(RATIOS.to_vec()),
+        ) {
+            check_ratio_points(case, ratio)?;
+        }
+
+        #[test]
+        fn ratio_points_match_brute_force_3d(
+            case in antennas::<3>(7),
+            ratio in proptest::sample::select(RATIOS.to_vec()),
+        ) {
+            check_ratio_points(case, ratio)?;
+        }
+    }
+}
@@ -1,202 +1,256 @@
+mod geometry;
+mod render;
+
+use geometry::{line_points, ratio_points, Bounds, Position};
+use regex::Regex;
+use render::{write_map, MapFormat};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
+use std::sync::LazyLock;
+
+// Layer, row and column. A single map is just one layer.
+type Point = Position<3>;
+type Layers = Vec<Vec<Vec<char>>>;
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-struct Position {
-    i: i32,
-    j: i32,
+static ANTENNA_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[a-zA-Z0-9]").unwrap());
+
+// An antinode is placed wherever one antenna is `far / near` times as far as the other
+#[derive(Debug, Clone, Copy)]
+struct Ratio {
+    far: i32,
+    near: i32,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-struct Delta {
-    di: i32,
-    dj: i32,
+// What to print besides the answer
+struct OutputOptions {
+    map_format: Option<MapFormat>,
+    map_file: Option<String>,
+    coords: bool,
+    stats: bool,
+    // Antinodes found for each ratio, when they were picked with --ratios
+    ratio_counts: bool,
 }
 
-impl std::ops::Add<Delta> for Position {
-    type Output = Position;
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let input_file = args.get(1).expect("Input file is required");
+    let input = parse_input(input_file);
 
-    fn add(self, rhs: Delta) -> Self::Output {
-        Position {
-            i: self.i + rhs.di,
-            j: self.j + rhs.dj,
-        }
+    let get_arg = |name: &str| {
+        args.iter().position(|a| a == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {}", name))
+        })
+    };
+    let options = OutputOptions {
+        map_format: get_arg("--map")
+            .map(|f| MapFormat::parse(f).unwrap_or_else(|| panic!("Unknown map format: {}", f))),
+        map_file: get_arg("--map-file").cloned(),
+        coords: args.contains(&"--coords".to_string()),
+        stats: args.contains(&"--stats".to_string()),
+        ratio_counts: get_arg("--ratios").is_some(),
+    };
+
+    if args.contains(&"--part-2".to_string()) {
+        assert!(
+            !options.ratio_counts,
+            "--ratios only applies to part 1, part 2 takes every point in line"
+        );
+        println!("{:?}", part_2(input, &options));
+    } else {
+        let ratios = match get_arg("--ratios") {
+            Some(spec) => parse_ratios(spec),
+            None => vec![Ratio { far: 2, near: 1 }],
+        };
+        println!("{:?}", part_1(input, &ratios, &options));
     }
 }
 
-impl std::ops::Sub<Delta> for Position {
-    type Output = Position;
-
-    fn sub(self, rhs: Delta) -> Self::Output {
-        self + (-rhs)
-    }
+// e.g. "2:1,3:1,3:2"
+fn parse_ratios(spec: &str) -> Vec<Ratio> {
+    spec.split(',')
+        .map(|r| {
+            let (far, near) = r.split_once(':').expect("Ratios must look like far:near");
+            let ratio = Ratio {
+                far: far.trim().parse().expect("Failed to parse ratio"),
+                near: near.trim().parse().expect("Failed to parse ratio"),
+            };
+            assert!(ratio.far > 0 && ratio.near > 0, "Ratios must be positive");
+            ratio
+        })
+        .collect()
 }
 
-impl std::ops::Neg for Delta {
-    type Output = Delta;
+// Stacked floor plans are separated by blank lines, one grid per layer
+fn parse_input(input_file: &str) -> Layers {
+    let layers: Layers = fs::read_to_string(input_file)
+        .expect("Failed to read file")
+        .replace("\r\n", "\n")
+        .split("\n\n")
+        .filter(|layer| !layer.trim().is_empty())
+        .map(|layer| layer.lines().map(|l| l.chars().collect()).collect())
+        .collect();
 
-    fn neg(self) -> Self::Output {
-        Delta {
-            di: -self.di,
-            dj: -self.dj,
-        }
-    }
+    let n_rows = layers[0].len();
+    let n_cols = layers[0][0].len();
+    assert!(
+        layers
+            .iter()
+            .all(|layer| layer.len() == n_rows && layer.iter().all(|row| row.len() == n_cols)),
+        "All layers must have the same size"
+    );
+    layers
 }
 
-impl std::ops::Div<i32> for Delta {
-    type Output = Delta;
+fn get_bounds(input: &Layers) -> Bounds<3> {
+    Bounds([
+        input.len() as i32,
+        input[0].len() as i32,
+        input[0][0].len() as i32,
+    ])
+}
+
+fn part_1(input: Layers, ratios: &[Ratio], options: &OutputOptions) -> i32 {
+    let antennas = get_antennas(&input);
+    let bounds = get_bounds(&input);
+
+    let mut antinodes: HashMap<char, HashSet<Point>> = HashMap::new();
+    for ratio in ratios {
+        // The other ordering of each pair takes care of the symmetric antinodes
+        let ratio_antinodes = find_antinodes(&antennas, |p1, p2| {
+            ratio_points(p1, p2, ratio.far, ratio.near)
+                .into_iter()
+                .filter(|&p| bounds.contains(p))
+                .collect()
+        });
 
-    fn div(self, rhs: i32) -> Self::Output {
-        Delta {
-            di: self.di / rhs,
-            dj: self.dj / rhs,
+        if options.ratio_counts {
+            let count = ratio_antinodes
+                .values()
+                .flatten()
+                .collect::<HashSet<_>>()
+                .len();
+            println!("{}:{} -> {}", ratio.far, ratio.near, count);
+        }
+
+        for (freq, positions) in ratio_antinodes {
+            antinodes.entry(freq).or_default().extend(positions);
         }
     }
+
+    solve(&input, &antennas, &antinodes, options)
 }
 
-impl std::ops::Mul<i32> for Delta {
-    type Output = Delta;
+fn part_2(input: Layers, options: &OutputOptions) -> i32 {
+    let antennas = get_antennas(&input);
+    let bounds = get_bounds(&input);
 
-    fn mul(self, rhs: i32) -> Self::Output {
-        Delta {
-            di: self.di * rhs,
-            dj: self.dj * rhs,
-        }
-    }
+    // Every grid point in line with the two antennas, including
+    // the antennas themselves and the points between them
+    let antinodes = find_antinodes(&antennas, |p1, p2| {
+        line_points(p1, p2, |p| bounds.contains(p))
+    });
+
+    solve(&input, &antennas, &antinodes, options)
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let input_file = args.get(1).expect("Input file is required");
-    let input = parse_input(input_file);
+fn solve(
+    input: &Layers,
+    antennas: &HashMap<char, HashSet<Point>>,
+    antinodes: &HashMap<char, HashSet<Point>>,
+    options: &OutputOptions,
+) -> i32 {
+    let all_antinodes: HashSet<Point> = antinodes.values().flatten().copied().collect();
 
-    if args.contains(&"--part-2".to_string()) {
-        println!("{:?}", part_2(input));
-    } else {
-        println!("{:?}", part_1(input));
+    if options.coords {
+        let mut coords: Vec<&Point> = all_antinodes.iter().collect();
+        coords.sort();
+        for Position([layer, i, j]) in coords {
+            // Flat maps keep the familiar 2D coordinates
+            if input.len() == 1 {
+                println!("({},{})", i, j);
+            } else {
+                println!("({},{},{})", layer, i, j);
+            }
+        }
     }
-}
 
-fn parse_input(input_file: &str) -> Vec<Vec<char>> {
-    fs::read_to_string(input_file)
-        .expect("Failed to read file")
-        .lines()
-        .map(|l| l.chars().collect())
-        .collect()
-}
+    if options.stats {
+        print_stats(antennas, antinodes);
+    }
 
-fn part_1(input: Vec<Vec<char>>) -> i32 {
-    solve(input, false)
-}
+    if let Some(format) = &options.map_format {
+        write_map(input, antinodes, format, options.map_file.as_deref());
+    }
 
-fn part_2(input: Vec<Vec<char>>) -> i32 {
-    solve(input, true)
+    all_antinodes.len() as i32
 }
 
-fn solve(mut input: Vec<Vec<char>>, part_2: bool) -> i32 {
-    let antennas = get_antennas(&input);
-    let n_rows = input.len();
-    let n_cols = input[0].len();
-
-    let result = find_antinodes(&antennas, n_rows as i32, n_cols as i32, part_2)
-        .into_iter()
-        .filter(|a| a.i >= 0 && a.i < n_rows as i32 && a.j >= 0 && a.j < n_cols as i32)
-        .map(|a| {
-            println!("({},{})", a.i, a.j);
-            if input[a.i as usize][a.j as usize] == '.' {
-                input[a.i as usize][a.j as usize] = '#';
-            }
-            a
-        })
-        .count() as i32;
+// Antennas, antinodes and antinodes shared with other frequencies, for each frequency
+fn print_stats(
+    antennas: &HashMap<char, HashSet<Point>>,
+    antinodes: &HashMap<char, HashSet<Point>>,
+) {
+    let mut freqs: Vec<&char> = antennas.keys().collect();
+    freqs.sort();
 
-    print_map(&input);
-    result
+    println!("freq\tantennas\tantinodes\tshared");
+    for freq in freqs {
+        let own = antinodes.get(freq).cloned().unwrap_or_default();
+        let shared = own
+            .iter()
+            .filter(|p| {
+                antinodes
+                    .iter()
+                    .any(|(other, positions)| other != freq && positions.contains(p))
+            })
+            .count();
+        println!(
+            "{}\t{}\t{}\t{}",
+            freq,
+            antennas[freq].len(),
+            own.len(),
+            shared
+        );
+    }
 }
 
-fn get_antennas(map: &Vec<Vec<char>>) -> HashMap<char, HashSet<Position>> {
+fn get_antennas(input: &Layers) -> HashMap<char, HashSet<Point>> {
     let mut antennas = HashMap::new();
-    for (i, row) in map.iter().enumerate() {
-        for (j, &freq) in row.iter().enumerate() {
-            if regex::Regex::new(r"[a-zA-Z0-9]")
-                .unwrap()
-                .is_match(freq.to_string().as_str())
-            {
-                antennas
-                    .entry(freq)
-                    .or_insert(HashSet::new())
-                    .insert(Position {
-                        i: i as i32,
-                        j: j as i32,
-                    });
+    for (layer, map) in input.iter().enumerate() {
+        for (i, row) in map.iter().enumerate() {
+            for (j, &freq) in row.iter().enumerate() {
+                if ANTENNA_PATTERN.is_match(freq.to_string().as_str()) {
+                    antennas
+                        .entry(freq)
+                        .or_insert(HashSet::new())
+                        .insert(Position([layer as i32, i as i32, j as i32]));
+                }
             }
         }
     }
     antennas
 }
 
+// Runs `antinodes_of` on every ordered pair of antennas with the same frequency
 fn find_antinodes(
-    antennas: &HashMap<char, HashSet<Position>>,
-    max_rows: i32,
-    max_cols: i32,
-    part_2: bool,
-) -> HashSet<Position> {
-    let mut antinodes = HashSet::new();
-
-    for positions in antennas.values() {
+    antennas: &HashMap<char, HashSet<Point>>,
+    antinodes_of: impl Fn(Point, Point) -> Vec<Point>,
+) -> HashMap<char, HashSet<Point>> {
+    let mut antinodes = HashMap::new();
+
+    for (&freq, positions) in antennas {
+        let freq_antinodes: &mut HashSet<Point> = antinodes.entry(freq).or_default();
         for p1 in positions {
             for p2 in positions {
                 if p1 == p2 {
                     continue;
                 }
 
-                let di = p2.i as i32 - p1.i as i32;
-                let dj = p2.j as i32 - p1.j as i32;
-
-                let delta = Delta { di, dj };
-
-                if !part_2 {
-                    antinodes.insert(*p2 + delta);
-                    antinodes.insert(*p1 - delta);
-                } else {
-                    // The antenas themselves are antinodes
-                    antinodes.insert(*p1);
-                    antinodes.insert(*p2);
-
-                    // Add all antinodes in line with the two antennas
-                    // that are within the grid
-                    let mut pi = *p2 + delta;
-                    while in_bounds(pi, max_rows, max_cols) {
-                        antinodes.insert(pi);
-                        pi = pi + delta;
-                    }
-
-                    pi = *p1 - delta;
-                    while in_bounds(pi, max_rows, max_cols) {
-                        antinodes.insert(pi);
-                        pi = pi - delta;
-                    }
-                }
-
-                // Should not forget antinodes *between* antennas
-                if di % 3 == 0 && dj % 3 == 0 {
-                    let delta_third = delta / 3;
-                    antinodes.insert(*p1 + delta_third);
-                    antinodes.insert(*p2 - delta_third);
-                }
+                freq_antinodes.extend(antinodes_of(*p1, *p2));
             }
         }
     }
     antinodes
 }
-
-fn in_bounds(p: Position, max_rows: i32, max_cols: i32) -> bool {
-    p.i >= 0 && p.i < max_rows && p.j >= 0 && p.j < max_cols
-}
-
-fn print_map(map: &Vec<Vec<char>>) {
-    for row in map {
-        println!("{}", row.iter().collect::<String>());
-    }
-}
@@ -0,0 +1,199 @@
+use crate::geometry::Position;
+use crate::{Layers, Point};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+
+// Side of the square each grid cell takes in a PPM image
+const PPM_CELL_SIZE: usize = 8;
+const SHARED_COLOUR: (u8, u8, u8) = (255, 255, 255);
+const SEPARATOR_COLOUR: [u8; 3] = [40, 40, 40];
+
+pub enum MapFormat {
+    Text,
+    Ansi,
+    Ppm,
+}
+
+impl MapFormat {
+    pub fn parse(name: &str) -> Option<MapFormat> {
+        match name {
+            "text" => Some(MapFormat::Text),
+            "ansi" => Some(MapFormat::Ansi),
+            "ppm" => Some(MapFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+// Writes the map to `output_file`, or to stdout if there is none
+pub fn write_map(
+    layers: &Layers,
+    antinodes: &HashMap<char, HashSet<Point>>,
+    format: &MapFormat,
+    output_file: Option<&str>,
+) {
+    let bytes = match format {
+        MapFormat::Text => render_text(layers, antinodes).into_bytes(),
+        MapFormat::Ansi => render_ansi(layers, antinodes).into_bytes(),
+        MapFormat::Ppm => render_ppm(layers, antinodes),
+    };
+
+    match output_file {
+        Some(path) => fs::write(path, bytes).expect("Failed to write map"),
+        None => std::io::stdout()
+            .write_all(&bytes)
+            .expect("Failed to write map"),
+    }
+}
+
+// Antennas keep their letter, free cells with an antinode become '#'.
+// Layers are separated by a blank line, like in the input.
+fn render_text(layers: &Layers, antinodes: &HashMap<char, HashSet<Point>>) -> String {
+    let owners = antinode_owners(antinodes);
+    let mut out = String::new();
+    for (layer, map) in layers.iter().enumerate() {
+        if layer > 0 {
+            out.push('\n');
+        }
+        for (i, row) in map.iter().enumerate() {
+            for (j, &c) in row.iter().enumerate() {
+                let p = to_position(layer, i, j);
+                if c == '.' && owners.contains_key(&p) {
+                    out.push('#');
+                } else {
+                    out.push(c);
+                }
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// Same as the text map, but antennas and antinodes are coloured by frequency.
+// Antinodes of more than one frequency are shown in white.
+fn render_ansi(layers: &Layers, antinodes: &HashMap<char, HashSet<Point>>) -> String {
+    let colours = frequency_colours(layers, antinodes);
+    let owners = antinode_owners(antinodes);
+    let mut out = String::new();
+    for (layer, map) in layers.iter().enumerate() {
+        if layer > 0 {
+            out.push('\n');
+        }
+        for (i, row) in map.iter().enumerate() {
+            for (j, &c) in row.iter().enumerate() {
+                let p = to_position(layer, i, j);
+                let (symbol, colour) = match (c, owners.get(&p)) {
+                    ('.', None) => {
+                        out.push('.');
+                        continue;
+                    }
+                    ('.', Some(freqs)) => ('#', owner_colour(freqs, &colours)),
+                    (c, _) => (c, colours[&c]),
+                };
+                let (r, g, b) = colour;
+                out.push_str(&format!("\x1b[1;38;2;{};{};{}m{}\x1b[0m", r, g, b, symbol));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// Antennas are drawn in their frequency colour, antinodes in a darker shade of it.
+// Layers are stacked from top to bottom, with an empty row of cells between them.
+fn render_ppm(layers: &Layers, antinodes: &HashMap<char, HashSet<Point>>) -> Vec<u8> {
+    let colours = frequency_colours(layers, antinodes);
+    let owners = antinode_owners(antinodes);
+    let n_rows = layers[0].len();
+    let n_cols = layers[0].first().map_or(0, |row| row.len());
+    let height = (layers.len() * (n_rows + 1) - 1) * PPM_CELL_SIZE;
+    let width = n_cols * PPM_CELL_SIZE;
+
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for y in 0..height {
+        for x in 0..width {
+            let (layer, i) = (
+                (y / PPM_CELL_SIZE) / (n_rows + 1),
+                (y / PPM_CELL_SIZE) % (n_rows + 1),
+            );
+            let j = x / PPM_CELL_SIZE;
+            if i == n_rows {
+                image.extend(SEPARATOR_COLOUR);
+                continue;
+            }
+            let p = to_position(layer, i, j);
+            let (r, g, b) = match (layers[layer][i][j], owners.get(&p)) {
+                ('.', None) => (0, 0, 0),
+                ('.', Some(freqs)) => {
+                    let (r, g, b) = owner_colour(freqs, &colours);
+                    (r / 2, g / 2, b / 2)
+                }
+                (c, _) => colours[&c],
+            };
+            image.extend([r, g, b]);
+        }
+    }
+    image
+}
+
+// Which frequencies have an antinode in each position
+fn antinode_owners(antinodes: &HashMap<char, HashSet<Point>>) -> HashMap<Point, Vec<char>> {
+    let mut owners: HashMap<Point, Vec<char>> = HashMap::new();
+    for (&freq, positions) in antinodes {
+        for &p in positions {
+            owners.entry(p).or_default().push(freq);
+        }
+    }
+    owners
+}
+
+fn owner_colour(freqs: &[char], colours: &HashMap<char, (u8, u8, u8)>) -> (u8, u8, u8) {
+    match freqs {
+        [freq] => colours[freq],
+        _ => SHARED_COLOUR,
+    }
+}
+
+// Spreads the frequencies evenly around the colour wheel
+fn frequency_colours(
+    layers: &Layers,
+    antinodes: &HashMap<char, HashSet<Point>>,
+) -> HashMap<char, (u8, u8, u8)> {
+    let mut freqs: Vec<char> = layers
+        .iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .filter(|&c| c != '.')
+        .chain(antinodes.keys().copied())
+        .collect();
+    freqs.sort();
+    freqs.dedup();
+
+    let n = freqs.len().max(1) as f64;
+    freqs
+        .into_iter()
+        .enumerate()
+        .map(|(k, freq)| (freq, hue_to_rgb(k as f64 / n * 360.0)))
+        .collect()
+}
+
+fn hue_to_rgb(hue: f64) -> (u8, u8, u8) {
+    let x = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let scale = |v: f64| (55.0 + v * 200.0) as u8;
+    (scale(r), scale(g), scale(b))
+}
+
+fn to_position(layer: usize, i: usize, j: usize) -> Point {
+    Position([layer as i32, i as i32, j as i32])
+}
@@ -0,0 +1,86 @@
+use std::io::BufRead;
+
+// Reads the sizes in a disk map one at a time, without loading the whole file.
+// Two formats are supported:
+// - the classic one, where every digit is a size (`2333133121414131402`)
+// - an extended one, where sizes are comma-separated integers (`2,33,3,1,100`)
+// The format is detected by looking for a comma at the start of the file.
+// Whitespace (including the trailing newline) is ignored in both.
+pub struct DiskMapReader<R: BufRead> {
+    reader: R,
+    comma_separated: bool,
+    position: usize,
+}
+
+impl<R: BufRead> DiskMapReader<R> {
+    pub fn new(mut reader: R) -> DiskMapReader<R> {
+        let comma_separated = reader
+            .fill_buf()
+            .expect("Failed to read input file")
+            .contains(&b',');
+        DiskMapReader {
+            reader,
+            comma_separated,
+            position: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = *self
+            .reader
+            .fill_buf()
+            .expect("Failed to read input file")
+            .first()?;
+        self.reader.consume(1);
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn invalid(&self, byte: u8) -> ! {
+        panic!(
+            "Invalid character {:?} in disk map at byte {}",
+            byte as char,
+            self.position - 1
+        )
+    }
+}
+
+impl<R: BufRead> Iterator for DiskMapReader<R> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if !self.comma_separated {
+            loop {
+                match self.next_byte()? {
+                    b if b.is_ascii_whitespace() => continue,
+                    b if b.is_ascii_digit() => return Some((b - b'0') as u32),
+                    b => self.invalid(b),
+                }
+            }
+        }
+
+        let mut size: Option<u32> = None;
+        loop {
+            match self.next_byte() {
+                Some(b) if b.is_ascii_whitespace() => continue,
+                Some(b) if b.is_ascii_digit() => {
+                    let digit = (b - b'0') as u32;
+                    let value = size
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|v| v.checked_add(digit));
+                    size = Some(value.unwrap_or_else(|| {
+                        panic!("Size too large in disk map at byte {}", self.position - 1)
+                    }));
+                }
+                Some(b',') => match size {
+                    Some(size) => return Some(size),
+                    None => panic!("Empty size in disk map at byte {}", self.position - 1),
+                },
+                Some(b) => self.invalid(b),
+                // A trailing comma is fine
+                None => return size,
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::fs;
+
+// Longest disk that is still printed as a string of blocks
+pub const MAX_LAYOUT_LEN: usize = 200;
+
+// Symbols for the file ids in the block string. Ids past the last symbol wrap around.
+const FILE_SYMBOLS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const FREE_COLOUR: [u8; 3] = [0, 0, 0];
+const SEPARATOR_COLOUR: [u8; 3] = [255, 255, 255];
+
+// File id stored in each block of the disk, None for free blocks
+pub type Blocks = Vec<Option<u64>>;
+
+#[derive(Debug)]
+pub struct FragmentationSummary {
+    pub free_spans: usize,
+    pub largest_free_span: usize,
+    pub unmoved_files: usize,
+}
+
+// The classic `00...111...2...` representation
+pub fn render_layout(blocks: &Blocks) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Some(id) => FILE_SYMBOLS[(*id as usize) % FILE_SYMBOLS.len()] as char,
+            None => '.',
+        })
+        .collect()
+}
+
+// Writes a PPM image with the layout before compaction on top and the one after
+// compaction below it. Blocks are laid out row by row in a square-ish image, each
+// file with its own colour and free blocks in black.
+pub fn write_image(path: &str, before: &Blocks, after: &Blocks) {
+    let n_blocks = before.len().max(after.len());
+    let width = (n_blocks as f64).sqrt().ceil().max(1.0) as usize;
+    let panel_height = n_blocks.div_ceil(width);
+    let height = 2 * panel_height + 1;
+
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for (k, panel) in [before, after].into_iter().enumerate() {
+        for block in 0..panel_height * width {
+            let colour = match panel.get(block) {
+                Some(Some(id)) => file_colour(*id),
+                _ => FREE_COLOUR,
+            };
+            image.extend(colour);
+        }
+        if k == 0 {
+            for _ in 0..width {
+                image.extend(SEPARATOR_COLOUR);
+            }
+        }
+    }
+
+    fs::write(path, image).expect("Failed to write image");
+}
+
+// Free spans only count the gaps before the last used block:
+// the free space at the end of the disk is not fragmentation.
+pub fn summarize(before: &Blocks, after: &Blocks) -> FragmentationSummary {
+    let used_len = after.iter().rposition(|b| b.is_some()).map_or(0, |k| k + 1);
+    let mut free_spans = 0;
+    let mut largest_free_span = 0;
+    let mut curr_span = 0;
+    for block in &after[..used_len] {
+        if block.is_some() {
+            curr_span = 0;
+            continue;
+        }
+        if curr_span == 0 {
+            free_spans += 1;
+        }
+        curr_span += 1;
+        largest_free_span = largest_free_span.max(curr_span);
+    }
+
+    FragmentationSummary {
+        free_spans,
+        largest_free_span,
+        unmoved_files: count_unmoved_files(before, after),
+    }
+}
+
+// A file did not move if every one of its blocks is still in the same place
+fn count_unmoved_files(before: &Blocks, after: &Blocks) -> usize {
+    let n_files = before.iter().flatten().max().map_or(0, |id| id + 1) as usize;
+    let mut moved = vec![false; n_files];
+    for k in 0..before.len().max(after.len()) {
+        let old = before.get(k).copied().flatten();
+        let new = after.get(k).copied().flatten();
+        if old != new {
+            for id in [old, new].into_iter().flatten() {
+                moved[id as usize] = true;
+            }
+        }
+    }
+    moved.iter().filter(|&&m| !m).count()
+}
+
+// Spreads consecutive ids apart so neighbouring files are easy to tell apart
+fn file_colour(id: u64) -> [u8; 3] {
+    let hash = id.wrapping_mul(0x9E3779B97F4A7C15);
+    let [r, g, b, ..] = hash.to_be_bytes();
+    // Keep colours away from black, which is used for free blocks
+    [r | 0x40, g | 0x40, b | 0x40]
+}
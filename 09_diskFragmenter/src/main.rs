@@ -1,72 +1,190 @@
-#![feature(linked_list_cursors)]
+mod disk_map;
+mod layout;
+mod spaces;
 
+use disk_map::DiskMapReader;
+use layout::{render_layout, summarize, write_image, Blocks, MAX_LAYOUT_LEN};
+use spaces::SpaceTree;
 use std::cmp;
-use std::collections::LinkedList;
 use std::env;
 use std::fs;
+use std::io::BufReader;
+
+// How a file picks one of the free spaces to its left that are big enough for it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+    // Leftmost space (part 2)
+    FirstFit,
+    // Smallest space, leftmost if there is a tie
+    BestFit,
+    // Largest space, leftmost if there is a tie
+    WorstFit,
+    // First fit, repeated until no file can move. Spaces freed by a pass
+    // (merged with their neighbours) can be used by the next one.
+    MultiPass,
+}
+
+impl Strategy {
+    const ALL: [Strategy; 4] = [
+        Strategy::FirstFit,
+        Strategy::BestFit,
+        Strategy::WorstFit,
+        Strategy::MultiPass,
+    ];
+
+    fn parse(name: &str) -> Option<Strategy> {
+        Strategy::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Strategy::FirstFit => "first-fit",
+            Strategy::BestFit => "best-fit",
+            Strategy::WorstFit => "worst-fit",
+            Strategy::MultiPass => "multi-pass",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CompactionStats {
+    moves: usize,
+    blocks_moved: u64,
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let input_file = args.get(1).expect("Input file is required");
     let input = parse_input(input_file);
 
-    if args.contains(&"--part-2".to_string()) {
-        println!("{:?}", part_2(input));
+    let get_arg = |name: &str| {
+        args.iter().position(|a| a == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {}", name))
+        })
+    };
+
+    // `--strategy all` compares every strategy
+    let strategies: Vec<Strategy> = match get_arg("--strategy").map(|s| s.as_str()) {
+        Some("all") => Strategy::ALL.to_vec(),
+        Some(name) => {
+            vec![Strategy::parse(name).unwrap_or_else(|| panic!("Unknown strategy: {}", name))]
+        }
+        None => vec![],
+    };
+
+    let is_part_2 = args.contains(&"--part-2".to_string());
+    if !strategies.is_empty() {
+        for &strategy in &strategies {
+            let (memory_map, stats) = compact_files(&input, strategy);
+            println!(
+                "{}: checksum {}, {} moves, {} blocks moved, fragmentation {:.4}",
+                strategy.name(),
+                files_checksum(&memory_map.files),
+                stats.moves,
+                stats.blocks_moved,
+                fragmentation_ratio(&memory_map.files)
+            );
+        }
+    } else if is_part_2 {
+        println!("{:?}", part_2(&input));
     } else {
-        println!("{:?}", part_1(input));
+        println!("{:?}", part_1(&input));
+    }
+
+    let show_layout = args.contains(&"--layout".to_string());
+    let show_summary = args.contains(&"--summary".to_string());
+    let image_file = get_arg("--image");
+
+    if show_layout || show_summary || image_file.is_some() {
+        let before = disk_blocks(&input);
+        // With several strategies, the layout of the first one is shown
+        let strategy = strategies.first().copied();
+        let after = if is_part_2 || strategy.is_some() {
+            let strategy = strategy.unwrap_or(Strategy::FirstFit);
+            file_blocks(&compact_files(&input, strategy).0.files, before.len())
+        } else {
+            compact_blocks(&before)
+        };
+
+        if show_layout {
+            if before.len() <= MAX_LAYOUT_LEN {
+                println!("{}", render_layout(&before));
+                println!("{}", render_layout(&after));
+            } else {
+                println!(
+                    "Disk has {} blocks, too many to print (use --image instead)",
+                    before.len()
+                );
+            }
+        }
+        if let Some(path) = image_file {
+            write_image(path, &before, &after);
+        }
+        if show_summary {
+            let summary = summarize(&before, &after);
+            println!("Free spans: {}", summary.free_spans);
+            println!("Largest free span: {}", summary.largest_free_span);
+            println!("Files that did not move: {}", summary.unmoved_files);
+        }
     }
 }
 
-fn parse_input(input_file: &str) -> Vec<char> {
-    fs::read_to_string(input_file)
-        .expect("Failed to read input file")
-        .chars()
-        .collect()
+// Size of every file and space, in disk map order. The sizes take 4 bytes per
+// entry (400 MB for 100 million entries), which is all part 1 needs. Moving whole
+// files (part 2 and `--strategy`) adds the files and the space tree, up to about
+// 30 more bytes per entry, and `--layout`, `--summary` and `--image` expand the
+// map into 16 bytes per block.
+fn parse_input(input_file: &str) -> Vec<u32> {
+    let file = fs::File::open(input_file).expect("Failed to read input file");
+    DiskMapReader::new(BufReader::new(file)).collect()
 }
 
-fn part_1(mut input: Vec<char>) -> u64 {
-    let mut checksum: u64 = 0;
+fn part_1(input: &[u32]) -> u128 {
+    // An empty disk map has nothing to move
+    if input.is_empty() {
+        return 0;
+    }
+
+    let mut checksum: u128 = 0;
     let mut i = 0; // iterator that's moving forward
     let mut j = input.len() - 1; // iterator that's moving backward
+    let mut j_remaining = input[j]; // blocks of entry j that haven't been moved yet
     let mut curr_offset: u64 = 0;
     while i <= j {
         let i_is_file = i % 2 == 0;
         if i_is_file {
             let i_file_id = i / 2;
-            let i_file_len = input[i].to_digit(10).expect("Failed to parse character");
+            // Part of the last file may already have been moved
+            let i_file_len = if i == j { j_remaining } else { input[i] };
             checksum += segment_checksum(curr_offset, i_file_len as u64, i_file_id as u64);
 
             i += 1;
             curr_offset += i_file_len as u64;
         } else {
-            let mut free_blocks = input[i]
-                .to_digit(10)
-                .expect("Failed to parse character")
-                .into();
-            let j_is_file = j % 2 == 0;
+            let mut free_blocks = input[i];
             while free_blocks > 0 && j > i {
-                if !j_is_file {
+                if !j.is_multiple_of(2) {
+                    // j is a gap (the map can end with one)
                     j -= 1;
+                    j_remaining = input[j];
                     // We need to check again if j > i
                     continue;
                 }
 
                 // j is a file
                 let j_file_id = j / 2;
-                let j_file_len = input[j].to_digit(10).expect("Failed to parse character");
-                let n_blocks: u32 = cmp::min(free_blocks, j_file_len);
+                let n_blocks: u32 = cmp::min(free_blocks, j_remaining);
 
                 checksum += segment_checksum(curr_offset, n_blocks as u64, j_file_id as u64);
 
                 curr_offset += n_blocks as u64;
-
-                let remaining_j_blocks = j_file_len - n_blocks;
-                input[j] =
-                    char::from_digit(remaining_j_blocks, 10).expect("Failed to convert to char");
+                j_remaining -= n_blocks;
 
                 free_blocks -= n_blocks;
-                if remaining_j_blocks == 0 {
+                if j_remaining == 0 {
                     j -= 2;
+                    j_remaining = input[j];
                 }
             }
             i += 1;
@@ -75,102 +193,216 @@ fn part_1(mut input: Vec<char>) -> u64 {
     checksum
 }
 
-fn part_2(input: Vec<char>) -> u64 {
+fn part_2(input: &[u32]) -> u128 {
+    let (memory_map, _) = compact_files(input, Strategy::FirstFit);
+    files_checksum(&memory_map.files)
+}
+
+fn files_checksum(files: &[File]) -> u128 {
+    files
+        .iter()
+        .map(|file| segment_checksum(file.offset, file.len as u64, file.file_id as u64))
+        .sum()
+}
+
+// Fraction of the disk up to the end of the last file that is free space.
+// 0 means all files are packed together at the start of the disk.
+fn fragmentation_ratio(files: &[File]) -> f64 {
+    let used_len = files
+        .iter()
+        .filter(|file| file.len > 0)
+        .map(|file| file.offset + file.len as u64)
+        .max()
+        .unwrap_or(0);
+    if used_len == 0 {
+        return 0.0;
+    }
+    let file_blocks: u64 = files.iter().map(|file| file.len as u64).sum();
+    (used_len - file_blocks) as f64 / used_len as f64
+}
+
+fn compact_files(input: &[u32], strategy: Strategy) -> (MemoryMap, CompactionStats) {
     let mut memory_map = get_memory_map(input);
-    let max_space_size = memory_map.max_space_size;
+    let mut stats = CompactionStats::default();
+
+    loop {
+        let moves = compaction_pass(&mut memory_map, strategy);
+        stats.moves += moves.len();
+        stats.blocks_moved += moves.iter().map(|&len| len as u64).sum::<u64>();
+
+        if strategy != Strategy::MultiPass || moves.is_empty() {
+            break;
+        }
+        memory_map.spaces = free_spaces(&memory_map.files);
+    }
 
-    // Reallocate files
-    for file in &mut memory_map.files {
-        if file.len > max_space_size {
+    (memory_map, stats)
+}
+
+// Tries to move every file once, starting from the one with the highest id.
+// Returns the length of each file that was moved.
+fn compaction_pass(memory_map: &mut MemoryMap, strategy: Strategy) -> Vec<u32> {
+    let mut moves = vec![];
+    let spaces = &mut memory_map.spaces;
+
+    for file in memory_map.files.iter_mut().rev() {
+        if file.len == 0 {
             continue;
         }
 
-        // TODO: Maybe update max_space_size after reallocating file
-        // It was not necessary for my input size
-        let mut space_cursor = memory_map.spaces.cursor_front_mut();
-        while let Some(space) = space_cursor.current() {
-            if space.offset > file.offset {
-                break;
-            }
-            if space.len >= file.len {
-                // Found space for file
-                file.offset = space.offset;
-                if file.len == space.len {
-                    space_cursor.remove_current();
-                } else {
-                    space.len -= file.len;
-                    space.offset += file.len as u64;
-                }
-                break;
-            }
-            space_cursor.move_next();
+        // Only spaces to the left of the file are considered
+        let len = file.len as u64;
+        let chosen = match strategy {
+            Strategy::FirstFit | Strategy::MultiPass => spaces.first_fit(len, file.offset),
+            Strategy::BestFit => spaces.best_fit(len, file.offset),
+            Strategy::WorstFit => spaces.worst_fit(len, file.offset),
+        };
+
+        if let Some(k) = chosen {
+            // Whatever is left of the space can still be used by other files
+            file.offset = spaces.offset(k);
+            spaces.take(k, len);
+            moves.push(file.len);
         }
     }
 
-    // Calculate checksum
-    memory_map
-        .files
-        .iter()
-        .map(|file| segment_checksum(file.offset, file.len.try_into().unwrap(), file.file_id))
-        .sum()
+    moves
+}
+
+// Free spaces between the files, wherever they are now
+fn free_spaces(files: &[File]) -> SpaceTree {
+    let mut spans: Vec<(u64, u32)> = files.iter().map(|f| (f.offset, f.len)).collect();
+    spans.sort();
+
+    let mut spaces = vec![];
+    let mut curr_offset = 0;
+    for (offset, len) in spans {
+        if offset > curr_offset {
+            spaces.push((curr_offset, offset - curr_offset));
+        }
+        curr_offset = curr_offset.max(offset + len as u64);
+    }
+    SpaceTree::new(spaces)
 }
 
 // Calculates the checksum of a memory segment (with only one file)
-fn segment_checksum(offset: u64, chunk_size: u64, file_id: u64) -> u64 {
+fn segment_checksum(offset: u64, chunk_size: u64, file_id: u64) -> u128 {
+    let (offset, chunk_size, file_id) = (offset as u128, chunk_size as u128, file_id as u128);
     ((chunk_size * chunk_size) + (2 * offset * chunk_size) - chunk_size) * file_id / 2
 }
 
 #[derive(Debug)]
 struct File {
-    file_id: u64,
-    len: usize,
     offset: u64,
+    file_id: u32,
+    len: u32,
 }
 
-#[derive(Debug)]
-struct Space {
-    offset: u64,
-    len: usize,
+// Expands the disk map into the file id of every block
+fn disk_blocks(input: &[u32]) -> Blocks {
+    let mut blocks = vec![];
+    for (i, &chunk_size) in input.iter().enumerate() {
+        let block = if i % 2 == 0 {
+            Some((i / 2) as u64)
+        } else {
+            None
+        };
+        blocks.extend(std::iter::repeat_n(block, chunk_size as usize));
+    }
+    blocks
+}
+
+fn file_blocks(files: &[File], disk_len: usize) -> Blocks {
+    let mut blocks = vec![None; disk_len];
+    for file in files {
+        let offset = file.offset as usize;
+        blocks[offset..offset + file.len as usize].fill(Some(file.file_id as u64));
+    }
+    blocks
+}
+
+// Same as part 1, but keeping the blocks instead of computing the checksum
+fn compact_blocks(blocks: &Blocks) -> Blocks {
+    let mut blocks = blocks.clone();
+    let mut i = 0;
+    let mut j = blocks.len();
+    loop {
+        while i < j && blocks[i].is_some() {
+            i += 1;
+        }
+        while j > i && blocks[j - 1].is_none() {
+            j -= 1;
+        }
+        if j - i < 2 {
+            break;
+        }
+        blocks.swap(i, j - 1);
+    }
+    blocks
 }
 
 #[derive(Debug)]
 struct MemoryMap {
-    files: LinkedList<File>,
-    spaces: LinkedList<Space>,
-    max_space_size: usize,
+    // Ordered by file id
+    files: Vec<File>,
+    // In a single pass, files can never be moved to the space they free up
+    // (it is to the right of every file that is yet to be moved), so it is
+    // not tracked.
+    spaces: SpaceTree,
 }
 
-fn get_memory_map(input: Vec<char>) -> MemoryMap {
-    let mut memory_map: MemoryMap = MemoryMap {
-        files: LinkedList::new(),
-        spaces: LinkedList::new(),
-        max_space_size: 0,
-    };
+fn get_memory_map(input: &[u32]) -> MemoryMap {
+    let mut files = Vec::new();
+    let mut spaces = Vec::new();
     let mut curr_offset: u64 = 0;
-    for i in 0..input.len() {
-        let chunk_size: usize = input[i]
-            .to_digit(10)
-            .expect("Failed to parse character")
-            .try_into()
-            .unwrap();
+    for (i, &chunk_size) in input.iter().enumerate() {
         if i % 2 == 0 {
-            memory_map.files.push_front(File {
-                file_id: (i / 2) as u64,
+            files.push(File {
+                file_id: (i / 2) as u32,
                 len: chunk_size,
                 offset: curr_offset,
             })
-        } else {
-            if chunk_size > 0 {
-                memory_map.spaces.push_back(Space {
-                    offset: curr_offset,
-                    len: chunk_size,
-                });
-
-                memory_map.max_space_size =
-                    cmp::max(memory_map.max_space_size, chunk_size as usize);
-            }
+        } else if chunk_size > 0 {
+            spaces.push((curr_offset, chunk_size as u64));
         }
         curr_offset += chunk_size as u64;
     }
-    memory_map
+    MemoryMap {
+        files,
+        spaces: SpaceTree::new(spaces),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn blocks_checksum(blocks: &Blocks) -> u128 {
+        blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(k, block)| block.map(|id| k as u128 * id as u128))
+            .sum()
+    }
+
+    #[test]
+    fn part_1_handles_trailing_gap() {
+        assert_eq!(part_1(&[1, 2, 3, 4, 5]), 60);
+        assert_eq!(part_1(&[12, 3, 45, 1]), 1530);
+    }
+
+    #[test]
+    fn empty_disk_map_has_no_checksum() {
+        assert_eq!(part_1(&[]), 0);
+        assert_eq!(part_2(&[]), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn part_1_matches_block_compaction(input in prop::collection::vec(0u32..20, 0..30)) {
+            let expected = blocks_checksum(&compact_blocks(&disk_blocks(&input)));
+            prop_assert_eq!(part_1(&input), expected);
+        }
+    }
 }
@@ -0,0 +1,146 @@
+// Free spaces ordered by offset, with a max segment tree over their sizes.
+// Finding the leftmost space where a file fits takes O(log n), no matter how
+// many different space sizes there are.
+#[derive(Debug)]
+pub struct SpaceTree {
+    offsets: Vec<u64>,
+    // max[1] is the root, the children of node k are 2k and 2k + 1, and the
+    // leaves (the sizes of the spaces) start at `n_leaves`
+    max: Vec<u64>,
+    n_leaves: usize,
+}
+
+impl SpaceTree {
+    // `spaces` are (offset, size) pairs, sorted by offset
+    pub fn new(spaces: Vec<(u64, u64)>) -> SpaceTree {
+        let n_leaves = spaces.len().next_power_of_two();
+        let (offsets, sizes): (Vec<u64>, Vec<u64>) = spaces.into_iter().unzip();
+
+        let mut max = vec![0; 2 * n_leaves];
+        max[n_leaves..n_leaves + sizes.len()].copy_from_slice(&sizes);
+        for k in (1..n_leaves).rev() {
+            max[k] = max[2 * k].max(max[2 * k + 1]);
+        }
+
+        SpaceTree {
+            offsets,
+            max,
+            n_leaves,
+        }
+    }
+
+    pub fn offset(&self, k: usize) -> u64 {
+        self.offsets[k]
+    }
+
+    // Uses the first `len` blocks of space `k`. The rest of it stays available
+    // (and keeps its place in the offset order).
+    pub fn take(&mut self, k: usize, len: u64) {
+        self.offsets[k] += len;
+
+        let mut node = self.n_leaves + k;
+        self.max[node] -= len;
+        while node > 1 {
+            node /= 2;
+            self.max[node] = self.max[2 * node].max(self.max[2 * node + 1]);
+        }
+    }
+
+    // Leftmost space with at least `len` blocks that starts before `before`
+    pub fn first_fit(&self, len: u64, before: u64) -> Option<usize> {
+        let k = self.leftmost_at_least(len)?;
+        (self.offsets[k] < before).then_some(k)
+    }
+
+    // Smallest space with at least `len` blocks that starts before `before`,
+    // leftmost if there is a tie. Subtrees without a big enough space are
+    // skipped, but this can still visit every space in the worst case.
+    pub fn best_fit(&self, len: u64, before: u64) -> Option<usize> {
+        let limit = self.limit(before);
+        let mut best: Option<usize> = None;
+        let mut stack = vec![1];
+        while let Some(node) = stack.pop() {
+            if self.max[node] < len || self.first_leaf(node) >= limit {
+                continue;
+            }
+            if node >= self.n_leaves {
+                let k = node - self.n_leaves;
+                if best.is_none_or(|b| self.size(k) < self.size(b)) {
+                    best = Some(k);
+                }
+                if self.size(k) == len {
+                    // Can't do better than an exact fit
+                    break;
+                }
+                continue;
+            }
+            // Right child first, so that the left one is visited first
+            stack.push(2 * node + 1);
+            stack.push(2 * node);
+        }
+        best
+    }
+
+    // Largest space with at least `len` blocks that starts before `before`,
+    // leftmost if there is a tie
+    pub fn worst_fit(&self, len: u64, before: u64) -> Option<usize> {
+        let largest = self.prefix_max(self.limit(before));
+        if largest < len {
+            return None;
+        }
+        // There is a space of this size before the limit, and none bigger,
+        // so the leftmost one of at least this size is before the limit too
+        self.leftmost_at_least(largest)
+    }
+
+    fn size(&self, k: usize) -> u64 {
+        self.max[self.n_leaves + k]
+    }
+
+    // Number of spaces that start before `offset`
+    fn limit(&self, offset: u64) -> usize {
+        self.offsets.partition_point(|&o| o < offset)
+    }
+
+    fn first_leaf(&self, node: usize) -> usize {
+        let mut node = node;
+        while node < self.n_leaves {
+            node *= 2;
+        }
+        node - self.n_leaves
+    }
+
+    fn leftmost_at_least(&self, len: u64) -> Option<usize> {
+        if self.max[1] < len {
+            return None;
+        }
+        let mut node = 1;
+        while node < self.n_leaves {
+            node = if self.max[2 * node] >= len {
+                2 * node
+            } else {
+                2 * node + 1
+            };
+        }
+        Some(node - self.n_leaves)
+    }
+
+    // Largest size among the first `limit` spaces
+    fn prefix_max(&self, limit: usize) -> u64 {
+        let (mut lo, mut hi) = (self.n_leaves, self.n_leaves + limit);
+        let mut largest = 0;
+        while lo < hi {
+            if lo % 2 == 1 {
+                largest = largest.max(self.max[lo]);
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                largest = largest.max(self.max[hi]);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        largest
+    }
+}
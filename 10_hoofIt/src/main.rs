@@ -1,121 +1,456 @@
-use std::collections::HashSet;
+mod peaks;
+mod scc;
+mod trails;
+
+use peaks::PeakSet;
+use scc::strongly_connected_components;
 use std::env;
 use std::fs;
+use trails::{cells_reaching_peak, enumerate_trails, longest_trail, LongestTrail, Trail};
+
+// Trails listed per trailhead in the JSON output, unless --max-trails says otherwise
+const DEFAULT_MAX_TRAILS: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    // Height of the trailheads
+    start: i64,
+    // Height of the peaks. A trail ends as soon as it reaches one.
+    end: i64,
+    // Allowed height difference between two consecutive cells of a trail
+    min_step: i64,
+    max_step: i64,
+    // Also move diagonally (8-connectivity)
+    diagonal: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            start: 0,
+            end: 9,
+            min_step: 1,
+            max_step: 1,
+            diagonal: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Trailhead {
+    i: usize,
+    j: usize,
+    // Number of peaks reachable from this trailhead, if peaks were tracked
+    score: Option<u64>,
+    // Number of distinct trails starting at this trailhead
+    rating: Rating,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rating {
+    Trails(u128),
+    // Some trail can go around in circles before reaching a peak
+    Infinite,
+    // More trails than fit in a u128
+    Overflow,
+}
+
+impl Rating {
+    fn add(self, other: Rating) -> Rating {
+        match (self, other) {
+            (Rating::Trails(a), Rating::Trails(b)) => {
+                a.checked_add(b).map_or(Rating::Overflow, Rating::Trails)
+            }
+            (Rating::Infinite, _) | (_, Rating::Infinite) => Rating::Infinite,
+            _ => Rating::Overflow,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Peak {
+    i: usize,
+    j: usize,
+    // Number of trailheads that can reach this peak
+    trailheads: u64,
+}
+
+#[derive(Debug)]
+struct Analysis {
+    trailheads: Vec<Trailhead>,
+    peaks: Vec<Peak>,
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let input_file = args.get(1).expect("Input file is required");
     let input = parse_input(input_file);
 
-    if args.contains(&"--part-2".to_string()) {
-        println!("{:?}", part_2(input));
+    let get_arg = |name: &str| {
+        args.iter().position(|a| a == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {}", name))
+        })
+    };
+
+    let mut rules = Rules::default();
+    if let Some(start) = get_arg("--start") {
+        rules.start = start.parse().expect("Failed to parse start height");
+    }
+    if let Some(end) = get_arg("--end") {
+        rules.end = end.parse().expect("Failed to parse end height");
+    }
+    if let Some(step) = get_arg("--step") {
+        (rules.min_step, rules.max_step) = parse_step_range(step);
+    }
+    rules.diagonal = args.contains(&"--diagonal".to_string());
+
+    let is_part_2 = args.contains(&"--part-2".to_string());
+    let output_json = args.contains(&"--json".to_string());
+    let output_table = args.contains(&"--table".to_string());
+    // Part 2 on its own only needs the ratings
+    let track_peaks = !is_part_2 || output_json || output_table;
+    let Analysis { trailheads, peaks } = analyze_trailheads(&input, &rules, track_peaks);
+
+    if output_json {
+        let max_trails = get_arg("--max-trails").map_or(DEFAULT_MAX_TRAILS, |n| {
+            n.parse().expect("Failed to parse trail limit")
+        });
+        println!(
+            "{}",
+            trails_json(&input, &rules, &trailheads, &peaks, max_trails)
+        );
+        return;
+    }
+
+    if output_table {
+        println!("i\tj\tscore\trating");
+        for t in &trailheads {
+            println!(
+                "{}\t{}\t{}\t{}",
+                t.i,
+                t.j,
+                score(t),
+                format_rating(t.rating)
+            );
+        }
+    }
+
+    if is_part_2 {
+        println!("{}", format_rating(part_2(&trailheads)));
     } else {
-        println!("{:?}", part_1(input));
+        println!("{:?}", part_1(&trailheads));
+    }
+}
+
+// "1" or a range like "0..=2" or "-2..=-1" (downhill)
+fn parse_step_range(spec: &str) -> (i64, i64) {
+    let (min, max) = spec.split_once("..=").unwrap_or((spec, spec));
+    let min: i64 = min.trim().parse().expect("Failed to parse step range");
+    let max: i64 = max.trim().parse().expect("Failed to parse step range");
+    assert!(min <= max, "Empty step range: {}", spec);
+    (min, max)
+}
+
+fn format_rating(rating: Rating) -> String {
+    match rating {
+        Rating::Trails(n) => n.to_string(),
+        Rating::Infinite => "infinite".to_string(),
+        Rating::Overflow => format!("overflow (more than {})", u128::MAX),
     }
 }
 
-fn parse_input(input_file: &str) -> Vec<Vec<u32>> {
-    fs::read_to_string(input_file)
-        .expect("Failed to read input file")
+// Every character is a height, unless the map has commas: then each line is a
+// list of comma-separated heights (which can have more than one digit)
+fn parse_input(input_file: &str) -> Vec<Vec<i64>> {
+    let content = fs::read_to_string(input_file).expect("Failed to read input file");
+    let comma_separated = content.contains(',');
+    content
         .lines()
+        .filter(|line| !line.trim().is_empty())
         .map(|line| {
-            line.chars()
-                .map(|c| c.to_digit(10).expect("Failed to parse digit"))
-                .collect()
+            if comma_separated {
+                line.split(',')
+                    .map(|h| h.trim().parse().expect("Failed to parse height"))
+                    .collect()
+            } else {
+                line.chars()
+                    .map(|c| c.to_digit(10).expect("Failed to parse digit") as i64)
+                    .collect()
+            }
         })
         .collect()
 }
 
-fn part_1(input: Vec<Vec<u32>>) -> u32 {
-    input
+fn score(trailhead: &Trailhead) -> u64 {
+    trailhead.score.expect("Peaks weren't tracked")
+}
+
+fn part_1(trailheads: &[Trailhead]) -> u64 {
+    trailheads.iter().map(score).sum()
+}
+
+fn part_2(trailheads: &[Trailhead]) -> Rating {
+    trailheads
         .iter()
-        .enumerate()
-        .map(|(i, line)| {
-            line.iter()
-                .enumerate()
-                .map(|(j, &c)| {
-                    return if c == 0 {
-                        println!("Found trail head at ({}, {})", i, j);
-                        let mut trail_ends: HashSet<(isize, isize)> = HashSet::new();
-                        search_trail_end(&input, i as isize, j as isize, &mut trail_ends);
-                        trail_ends.len() as u32
-                    } else {
-                        0
-                    };
+        .fold(Rating::Trails(0), |total, t| total.add(t.rating))
+}
+
+// Cells are grouped into strongly connected components, which are processed so
+// that every component a trail can step into is done before the ones stepping into
+// it. With the default rules (always one step up) every cell is its own component
+// and this is just going from the highest cells to the lowest. Each component keeps:
+// - the set of peaks it can reach, which is the union over its next steps. This is
+//   skipped unless `track_peaks` is set.
+// - the number of trails from it to a peak, which is the sum over its next steps.
+//   Components with more than one cell have a cycle, so if they can reach a peak
+//   there are infinitely many trails.
+// Peak sets are dropped once every component that steps into them is done.
+fn analyze_trailheads(input: &[Vec<i64>], rules: &Rules, track_peaks: bool) -> Analysis {
+    let n_cols = input.first().map_or(0, |row| row.len());
+    let n_cells = input.len() * n_cols;
+    let height = |cell: usize| input[cell / n_cols][cell % n_cols];
+    let next = |cell: usize| next_steps(input, cell / n_cols, cell % n_cols, rules);
+
+    let peak_ids: Vec<Option<usize>> = {
+        let mut n_peaks = 0;
+        (0..n_cells)
+            .map(|cell| {
+                (height(cell) == rules.end).then(|| {
+                    n_peaks += 1;
+                    n_peaks - 1
                 })
-                .sum::<u32>()
+            })
+            .collect()
+    };
+    let peak_cells: Vec<usize> = (0..n_cells)
+        .filter(|&cell| peak_ids[cell].is_some())
+        .collect();
+    let mut peak_trailheads: Vec<u64> = vec![0; peak_cells.len()];
+    let n_words = peak_cells.len().div_ceil(64);
+
+    let components = strongly_connected_components(n_cells, next);
+    let mut component_of = vec![0; n_cells];
+    for (c, component) in components.iter().enumerate() {
+        for &cell in component {
+            component_of[cell] = c;
+        }
+    }
+
+    // How many steps from other components lead into each component
+    let mut pending_steps_in = vec![0; components.len()];
+    for cell in 0..n_cells {
+        for next_cell in next(cell) {
+            if component_of[next_cell] != component_of[cell] {
+                pending_steps_in[component_of[next_cell]] += 1;
+            }
+        }
+    }
+
+    let mut peaks: Vec<Option<PeakSet>> = vec![None; components.len()];
+    let mut ratings: Vec<Rating> = vec![Rating::Trails(0); components.len()];
+    let mut trailheads = vec![];
+
+    for (c, component) in components.iter().enumerate() {
+        let mut reachable = PeakSet::default();
+        let mut rating = Rating::Trails(0);
+
+        for &cell in component {
+            if let Some(peak_id) = peak_ids[cell] {
+                if track_peaks {
+                    reachable.insert(peak_id);
+                }
+                rating = rating.add(Rating::Trails(1));
+            }
+            for next_cell in next(cell) {
+                let next_c = component_of[next_cell];
+                if next_c == c {
+                    continue;
+                }
+
+                if track_peaks {
+                    let next_peaks = peaks[next_c].as_ref().expect("Missing peaks");
+                    reachable.extend(next_peaks, n_words);
+                }
+                rating = rating.add(ratings[next_c]);
+
+                pending_steps_in[next_c] -= 1;
+                if pending_steps_in[next_c] == 0 {
+                    peaks[next_c] = None;
+                }
+            }
+        }
+        reachable.finish(n_words);
+
+        // A trail reaches a peak from here unless the rating is 0
+        if component.len() > 1 && rating != Rating::Trails(0) {
+            rating = Rating::Infinite;
+        }
+
+        let score = track_peaks.then(|| reachable.count());
+        let mut n_trailheads = 0;
+        for &cell in component {
+            if height(cell) == rules.start {
+                n_trailheads += 1;
+                trailheads.push(Trailhead {
+                    i: cell / n_cols,
+                    j: cell % n_cols,
+                    score,
+                    rating,
+                });
+            }
+        }
+
+        if n_trailheads > 0 {
+            reachable.for_each(|peak_id| peak_trailheads[peak_id] += n_trailheads);
+        }
+
+        ratings[c] = rating;
+        if track_peaks && pending_steps_in[c] > 0 {
+            peaks[c] = Some(reachable);
+        }
+    }
+
+    trailheads.sort_by_key(|t| (t.i, t.j));
+    let peaks = peak_cells
+        .iter()
+        .zip(peak_trailheads)
+        .map(|(&cell, trailheads)| Peak {
+            i: cell / n_cols,
+            j: cell % n_cols,
+            trailheads,
         })
-        .sum()
+        .collect();
+
+    Analysis { trailheads, peaks }
 }
 
-fn part_2(input: Vec<Vec<u32>>) -> u32 {
-    input
+// Trails of each trailhead, the longest trail and the peaks reachable from the
+// most trailheads. Coordinates are [i, j] pairs. Ratings are null when infinite
+// and "overflow" when they don't fit in a u128.
+fn trails_json(
+    input: &[Vec<i64>],
+    rules: &Rules,
+    trailheads: &[Trailhead],
+    peaks: &[Peak],
+    max_trails: usize,
+) -> String {
+    let reaches_peak = cells_reaching_peak(input, rules);
+    let coords = |(i, j): (usize, usize)| format!("[{},{}]", i, j);
+    let trail_json = |trail: &Trail| {
+        let cells: Vec<String> = trail.iter().map(|&cell| coords(cell)).collect();
+        format!("[{}]", cells.join(","))
+    };
+
+    let trailheads_json: Vec<String> = trailheads
         .iter()
-        .enumerate()
-        .map(|(i, line)| {
-            line.iter()
-                .enumerate()
-                .map(|(j, &c)| {
-                    return if c == 0 {
-                        count_paths(&input, i as isize, j as isize)
-                    } else {
-                        0
-                    };
-                })
-                .sum::<u32>()
+        .map(|t| {
+            let trails = enumerate_trails(input, rules, &reaches_peak, (t.i, t.j), max_trails);
+            let trails: Vec<String> = trails.iter().map(trail_json).collect();
+            format!(
+                "{{\"position\":{},\"score\":{},\"rating\":{},\"trails\":[{}]}}",
+                coords((t.i, t.j)),
+                score(t),
+                match t.rating {
+                    Rating::Trails(n) => n.to_string(),
+                    Rating::Infinite => "null".to_string(),
+                    Rating::Overflow => "\"overflow\"".to_string(),
+                },
+                trails.join(",")
+            )
         })
-        .sum()
+        .collect();
+
+    let longest_json = match longest_trail(input, rules, &reaches_peak) {
+        LongestTrail::NoTrail => "null".to_string(),
+        LongestTrail::SearchLimit => "\"search limit\"".to_string(),
+        LongestTrail::Found(trail) => format!(
+            "{{\"steps\":{},\"trail\":{}}}",
+            trail.len() - 1,
+            trail_json(&trail)
+        ),
+    };
+
+    let most_trailheads = peaks.iter().map(|p| p.trailheads).max().unwrap_or(0);
+    let top_peaks: Vec<String> = peaks
+        .iter()
+        .filter(|p| most_trailheads > 0 && p.trailheads == most_trailheads)
+        .map(|p| coords((p.i, p.j)))
+        .collect();
+
+    format!(
+        "{{\"trailheads\":[{}],\"longest_trail\":{},\"top_peaks\":{{\"trailheads\":{},\"peaks\":[{}]}}}}",
+        trailheads_json.join(","),
+        longest_json,
+        most_trailheads,
+        top_peaks.join(",")
+    )
 }
 
-fn search_trail_end(
-    input: &Vec<Vec<u32>>,
-    i: isize,
-    j: isize,
-    trail_ends: &mut HashSet<(isize, isize)>,
-) {
-    let current_height = input[i as usize][j as usize];
+// Neighbouring cells a trail can step into from (i, j)
+fn next_steps(input: &[Vec<i64>], i: usize, j: usize, rules: &Rules) -> Vec<usize> {
+    const STRAIGHT: [(isize, isize); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+    const DIAGONAL: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
 
-    if current_height == 9 {
-        trail_ends.insert((i, j));
-        return;
+    let current_height = input[i][j];
+    let n_cols = input[i].len();
+    let mut steps = vec![];
+
+    // The trail is over
+    if current_height == rules.end {
+        return steps;
     }
 
-    for (di, dj) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
-        let ni: isize = (i + di).try_into().unwrap();
-        let nj: isize = (j + dj).try_into().unwrap();
+    let directions = if rules.diagonal {
+        [STRAIGHT, DIAGONAL].concat()
+    } else {
+        STRAIGHT.to_vec()
+    };
+
+    for (di, dj) in directions {
+        let ni = i as isize + di;
+        let nj = j as isize + dj;
 
-        if ni >= 0
-            && nj >= 0
-            && ni < input.len() as isize
-            && nj < input[ni as usize].len() as isize
-            && input[ni as usize][nj as usize] == input[i as usize][j as usize] + 1
-        {
-            search_trail_end(&input, ni, nj, trail_ends);
+        if ni >= 0 && nj >= 0 && (ni as usize) < input.len() && (nj as usize) < n_cols {
+            let step = input[ni as usize][nj as usize] - current_height;
+            if (rules.min_step..=rules.max_step).contains(&step) {
+                steps.push(ni as usize * n_cols + nj as usize);
+            }
         }
     }
+    steps
 }
 
-fn count_paths(input: &Vec<Vec<u32>>, i: isize, j: isize) -> u32 {
-    let current_height = input[i as usize][j as usize];
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if current_height == 9 {
-        return 1;
+    // Two columns going up by one on each row. With diagonal steps every trail
+    // can switch columns at each step, so a trailhead has 2^(n_rows - 1) trails.
+    fn ladder(n_rows: i64) -> (Vec<Vec<i64>>, Rules) {
+        let input = (0..n_rows).map(|h| vec![h, h]).collect();
+        let rules = Rules {
+            end: n_rows - 1,
+            diagonal: true,
+            ..Rules::default()
+        };
+        (input, rules)
     }
 
-    let mut count = 0;
-    for (di, dj) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
-        let ni: isize = (i + di).try_into().unwrap();
-        let nj: isize = (j + dj).try_into().unwrap();
+    #[test]
+    fn too_many_trails_overflow() {
+        let (input, rules) = ladder(128);
+        let analysis = analyze_trailheads(&input, &rules, false);
+        let ratings: Vec<Rating> = analysis.trailheads.iter().map(|t| t.rating).collect();
+        assert_eq!(ratings, [Rating::Trails(1 << 127); 2]);
+        // Together they have 2^128 trails
+        assert_eq!(part_2(&analysis.trailheads), Rating::Overflow);
 
-        if ni >= 0
-            && nj >= 0
-            && ni < input.len() as isize
-            && nj < input[ni as usize].len() as isize
-            && input[ni as usize][nj as usize] == input[i as usize][j as usize] + 1
-        {
-            count += count_paths(&input, ni, nj)
-        }
+        let (input, rules) = ladder(130);
+        let analysis = analyze_trailheads(&input, &rules, true);
+        assert!(analysis
+            .trailheads
+            .iter()
+            .all(|t| t.rating == Rating::Overflow && t.score == Some(2)));
     }
-
-    count
 }
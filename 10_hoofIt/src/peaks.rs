@@ -0,0 +1,135 @@
+// Peaks reachable from a component, as peak ids. Most components only reach a
+// few peaks, so a set is a list of ids until it would take more room than a
+// bitset over every peak.
+#[derive(Debug, Clone)]
+pub enum PeakSet {
+    // Sorted and without duplicates, once `finish` is called
+    List(Vec<usize>),
+    // One bit per peak, in words of 64
+    Bits(Vec<u64>),
+}
+
+impl Default for PeakSet {
+    fn default() -> PeakSet {
+        PeakSet::List(vec![])
+    }
+}
+
+impl PeakSet {
+    pub fn insert(&mut self, id: usize) {
+        match self {
+            PeakSet::List(ids) => ids.push(id),
+            PeakSet::Bits(words) => words[id / 64] |= 1 << (id % 64),
+        }
+    }
+
+    // Adds the peaks of `other`, a finished set. `n_words` is the size of a
+    // bitset over every peak.
+    pub fn extend(&mut self, other: &PeakSet, n_words: usize) {
+        match other {
+            PeakSet::List(ids) => {
+                for &id in ids {
+                    self.insert(id);
+                }
+            }
+            PeakSet::Bits(other_words) => {
+                self.switch_to_bits(n_words);
+                if let PeakSet::Bits(words) = self {
+                    for (word, other_word) in words.iter_mut().zip(other_words) {
+                        *word |= other_word;
+                    }
+                }
+            }
+        }
+    }
+
+    // Drops duplicates from a list, and switches to a bitset if it's too long
+    pub fn finish(&mut self, n_words: usize) {
+        if let PeakSet::List(ids) = self {
+            ids.sort_unstable();
+            ids.dedup();
+            if ids.len() > n_words {
+                self.switch_to_bits(n_words);
+            }
+        }
+    }
+
+    fn switch_to_bits(&mut self, n_words: usize) {
+        if let PeakSet::List(ids) = self {
+            let mut words = vec![0u64; n_words];
+            for &id in ids.iter() {
+                words[id / 64] |= 1 << (id % 64);
+            }
+            *self = PeakSet::Bits(words);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        match self {
+            PeakSet::List(ids) => ids.len() as u64,
+            PeakSet::Bits(words) => words.iter().map(|w| w.count_ones() as u64).sum(),
+        }
+    }
+
+    // Ids of the peaks, going through the set bits of a bitset
+    pub fn for_each(&self, mut f: impl FnMut(usize)) {
+        match self {
+            PeakSet::List(ids) => ids.iter().for_each(|&id| f(id)),
+            PeakSet::Bits(words) => {
+                for (w, &word) in words.iter().enumerate() {
+                    let mut bits = word;
+                    while bits != 0 {
+                        f(w * 64 + bits.trailing_zeros() as usize);
+                        bits &= bits - 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+
+    fn ids(set: &PeakSet) -> Vec<usize> {
+        let mut ids = vec![];
+        set.for_each(|id| ids.push(id));
+        ids
+    }
+
+    proptest! {
+        // Sets built from peaks and from other sets, some of them long enough to
+        // become bitsets
+        #[test]
+        fn unions_match_btree_sets(
+            n_peaks in 1usize..300,
+            sets in prop::collection::vec(prop::collection::vec(any::<prop::sample::Index>(), 0..40), 1..8),
+        ) {
+            let n_words = n_peaks.div_ceil(64);
+            let mut finished: Vec<(PeakSet, BTreeSet<usize>)> = vec![];
+            for indices in sets {
+                let mut set = PeakSet::default();
+                let mut expected = BTreeSet::new();
+                for (k, index) in indices.iter().enumerate() {
+                    // Some of the ids come from the sets done before
+                    if k % 3 == 0 && !finished.is_empty() {
+                        let (other, other_expected) = &finished[index.index(finished.len())];
+                        set.extend(other, n_words);
+                        expected.extend(other_expected);
+                    } else {
+                        let id = index.index(n_peaks);
+                        set.insert(id);
+                        expected.insert(id);
+                    }
+                }
+                set.finish(n_words);
+                prop_assert_eq!(ids(&set), expected.iter().copied().collect::<Vec<_>>());
+                prop_assert_eq!(set.count(), expected.len() as u64);
+                finished.push((set, expected));
+            }
+        }
+    }
+}
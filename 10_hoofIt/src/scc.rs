@@ -0,0 +1,72 @@
+// Strongly connected components of the graph with nodes 0..n, using Tarjan's
+// algorithm. It is iterative, since big maps would overflow the stack.
+// Components come out in reverse topological order: all the components that
+// can be reached from a component come before it.
+pub fn strongly_connected_components(
+    n: usize,
+    neighbours: impl Fn(usize) -> Vec<usize>,
+) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+
+    let mut index = vec![UNVISITED; n];
+    let mut lowlink = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut next_index = 0;
+
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+
+        // Node, its neighbours and how many of them were already visited
+        let mut call_stack: Vec<(usize, Vec<usize>, usize)> = vec![];
+        index[root] = next_index;
+        lowlink[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        call_stack.push((root, neighbours(root), 0));
+
+        while let Some((v, v_neighbours, visited)) = call_stack.last_mut() {
+            let v = *v;
+
+            if let Some(&w) = v_neighbours.get(*visited) {
+                *visited += 1;
+                if index[w] == UNVISITED {
+                    index[w] = next_index;
+                    lowlink[w] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    call_stack.push((w, neighbours(w), 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+                continue;
+            }
+
+            // Done with v
+            call_stack.pop();
+            if let Some((parent, _, _)) = call_stack.last() {
+                lowlink[*parent] = lowlink[*parent].min(lowlink[v]);
+            }
+
+            if lowlink[v] == index[v] {
+                let mut component = vec![];
+                loop {
+                    let w = stack.pop().expect("Tarjan stack is empty");
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
+}
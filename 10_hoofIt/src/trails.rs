@@ -0,0 +1,309 @@
+use crate::scc::strongly_connected_components;
+use crate::{next_steps, Rules};
+use std::collections::{HashSet, VecDeque};
+
+// Cells from the trailhead to the peak, as (i, j)
+pub type Trail = Vec<(usize, usize)>;
+
+// Steps tried while looking for the longest trail through cycles, before giving up
+const MAX_SEARCH_STEPS: usize = 10_000_000;
+
+pub enum LongestTrail {
+    NoTrail,
+    // Trails through cycles have to be tried one by one, and there were too many
+    SearchLimit,
+    Found(Trail),
+}
+
+// Whether a trail going through each cell can still end in a peak.
+// Found by going backwards from the peaks.
+pub fn cells_reaching_peak(input: &[Vec<i64>], rules: &Rules) -> Vec<bool> {
+    let n_cols = input.first().map_or(0, |row| row.len());
+    let n_cells = input.len() * n_cols;
+
+    let mut previous_steps: Vec<Vec<usize>> = vec![vec![]; n_cells];
+    for cell in 0..n_cells {
+        for next_cell in next_steps(input, cell / n_cols, cell % n_cols, rules) {
+            previous_steps[next_cell].push(cell);
+        }
+    }
+
+    let mut reaches_peak = vec![false; n_cells];
+    let mut queue: VecDeque<usize> = (0..n_cells)
+        .filter(|&cell| input[cell / n_cols][cell % n_cols] == rules.end)
+        .collect();
+    for &cell in &queue {
+        reaches_peak[cell] = true;
+    }
+    while let Some(cell) = queue.pop_front() {
+        for &previous in &previous_steps[cell] {
+            if !reaches_peak[previous] {
+                reaches_peak[previous] = true;
+                queue.push_back(previous);
+            }
+        }
+    }
+    reaches_peak
+}
+
+// Up to `limit` trails starting at (i, j). Trails never visit the same cell
+// twice, so there is a finite number of them even when there are cycles.
+pub fn enumerate_trails(
+    input: &[Vec<i64>],
+    rules: &Rules,
+    reaches_peak: &[bool],
+    (i, j): (usize, usize),
+    limit: usize,
+) -> Vec<Trail> {
+    let n_cols = input[i].len();
+    let is_peak = |cell: usize| input[cell / n_cols][cell % n_cols] == rules.end;
+    let to_trail = |path: &[usize]| path.iter().map(|&c| (c / n_cols, c % n_cols)).collect();
+    let start = i * n_cols + j;
+
+    let mut trails = vec![];
+    if limit == 0 || !reaches_peak[start] {
+        return trails;
+    }
+    if is_peak(start) {
+        trails.push(to_trail(&[start]));
+        return trails;
+    }
+
+    // Depth-first search. For each cell in the current trail, `pending` has
+    // the next steps from it that are still to be tried.
+    let mut path: Vec<usize> = vec![start];
+    let mut on_path: HashSet<usize> = HashSet::from([start]);
+    let mut pending: Vec<Vec<usize>> = vec![next_candidates(input, rules, reaches_peak, start)];
+
+    while let Some(candidates) = pending.last_mut() {
+        match candidates.pop() {
+            Some(next_cell) => {
+                if on_path.contains(&next_cell) {
+                    continue;
+                }
+                path.push(next_cell);
+                on_path.insert(next_cell);
+
+                if is_peak(next_cell) {
+                    trails.push(to_trail(&path));
+                    if trails.len() == limit {
+                        break;
+                    }
+                }
+                pending.push(next_candidates(input, rules, reaches_peak, next_cell));
+            }
+            None => {
+                pending.pop();
+                let done = path.pop().expect("Empty trail");
+                on_path.remove(&done);
+            }
+        }
+    }
+
+    trails
+}
+
+// Next steps that can still end in a peak, in reverse order so that they are
+// popped in the usual direction order
+fn next_candidates(
+    input: &[Vec<i64>],
+    rules: &Rules,
+    reaches_peak: &[bool],
+    cell: usize,
+) -> Vec<usize> {
+    let n_cols = input[0].len();
+    let mut candidates: Vec<usize> = next_steps(input, cell / n_cols, cell % n_cols, rules)
+        .into_iter()
+        .filter(|&next_cell| reaches_peak[next_cell])
+        .collect();
+    candidates.reverse();
+    candidates
+}
+
+// Longest trail (in number of steps) from any trailhead to any peak. Like in
+// `enumerate_trails`, trails never visit the same cell twice.
+// Goes through the strongly connected components the same way as
+// `analyze_trailheads`, keeping the longest way to a peak from every cell. A trail
+// can't come back to a component it left, so it goes through each one in a single
+// stretch: from the cell it enters at, along cells of the component, and then out
+// to the next component or to a peak. Inside a component with cycles, every
+// stretch is tried, which only takes long for big components.
+pub fn longest_trail(input: &[Vec<i64>], rules: &Rules, reaches_peak: &[bool]) -> LongestTrail {
+    let n_cols = input.first().map_or(0, |row| row.len());
+    let n_cells = input.len() * n_cols;
+    let height = |cell: usize| input[cell / n_cols][cell % n_cols];
+    let next = |cell: usize| next_steps(input, cell / n_cols, cell % n_cols, rules);
+
+    let components = strongly_connected_components(n_cells, next);
+    let mut component_of = vec![0; n_cells];
+    for (c, component) in components.iter().enumerate() {
+        for &cell in component {
+            component_of[cell] = c;
+        }
+    }
+
+    // From each cell, the longest way to a peak (entering its component there):
+    // its number of steps, the cells after it in the component, and the first
+    // cell of the next component (None if the trail ends in the component)
+    let mut steps_to_peak: Vec<Option<usize>> = vec![None; n_cells];
+    let mut stretch: Vec<Vec<usize>> = vec![vec![]; n_cells];
+    let mut exit: Vec<Option<usize>> = vec![None; n_cells];
+    let mut search_steps = 0;
+
+    for (c, component) in components.iter().enumerate() {
+        for &entry in component {
+            if !reaches_peak[entry] {
+                continue;
+            }
+
+            // Depth-first search over the stretches from `entry`, like in
+            // `enumerate_trails`. Without cycles, the stretch is just `entry`.
+            let mut path: Vec<usize> = vec![entry];
+            let mut on_path: HashSet<usize> = HashSet::from([entry]);
+            let mut pending: Vec<Vec<usize>> = vec![next(entry)];
+            let mut best: Option<(usize, Vec<usize>, Option<usize>)> = None;
+            let mut consider = |steps: usize, path: &[usize], next_cell: Option<usize>| {
+                if best.as_ref().is_none_or(|(b, _, _)| steps > *b) {
+                    best = Some((steps, path[1..].to_vec(), next_cell));
+                }
+            };
+            if height(entry) == rules.end {
+                consider(0, &path, None);
+            }
+
+            while let Some(candidates) = pending.last_mut() {
+                let Some(next_cell) = candidates.pop() else {
+                    pending.pop();
+                    let done = path.pop().expect("Empty trail");
+                    on_path.remove(&done);
+                    continue;
+                };
+                let steps = path.len();
+                if component_of[next_cell] != c {
+                    if let Some(after) = steps_to_peak[next_cell] {
+                        consider(steps + after, &path, Some(next_cell));
+                    }
+                    continue;
+                }
+                if !reaches_peak[next_cell] || on_path.contains(&next_cell) {
+                    continue;
+                }
+
+                search_steps += 1;
+                if search_steps > MAX_SEARCH_STEPS {
+                    return LongestTrail::SearchLimit;
+                }
+                path.push(next_cell);
+                on_path.insert(next_cell);
+                if height(next_cell) == rules.end {
+                    consider(steps, &path, None);
+                }
+                pending.push(next(next_cell));
+            }
+
+            if let Some((steps, cells, next_cell)) = best {
+                steps_to_peak[entry] = Some(steps);
+                stretch[entry] = cells;
+                exit[entry] = next_cell;
+            }
+        }
+    }
+
+    let trailheads = (0..n_cells).filter(|&cell| height(cell) == rules.start);
+    let Some(start) = trailheads
+        .filter(|&cell| steps_to_peak[cell].is_some())
+        .max_by_key(|&cell| steps_to_peak[cell])
+    else {
+        return LongestTrail::NoTrail;
+    };
+    let mut trail = vec![start];
+    let mut cell = start;
+    loop {
+        trail.extend(&stretch[cell]);
+        let Some(next_cell) = exit[cell] else {
+            break;
+        };
+        trail.push(next_cell);
+        cell = next_cell;
+    }
+    LongestTrail::Found(trail.iter().map(|&c| (c / n_cols, c % n_cols)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn rules(min_step: i64, max_step: i64, diagonal: bool) -> Rules {
+        Rules {
+            min_step,
+            max_step,
+            diagonal,
+            ..Rules::default()
+        }
+    }
+
+    // Longest of every trail from every trailhead
+    fn brute_force_steps(input: &[Vec<i64>], rules: &Rules) -> Option<usize> {
+        let reaches_peak = cells_reaching_peak(input, rules);
+        let mut longest = None;
+        for (i, row) in input.iter().enumerate() {
+            for (j, &height) in row.iter().enumerate() {
+                if height != rules.start {
+                    continue;
+                }
+                for trail in enumerate_trails(input, rules, &reaches_peak, (i, j), usize::MAX) {
+                    longest = longest.max(Some(trail.len() - 1));
+                }
+            }
+        }
+        longest
+    }
+
+    fn check_trail(input: &[Vec<i64>], rules: &Rules, trail: &Trail) -> Result<(), TestCaseError> {
+        let n_cols = input[0].len();
+        let height = |(i, j): (usize, usize)| input[i][j];
+        prop_assert_eq!(height(trail[0]), rules.start);
+        prop_assert_eq!(height(trail[trail.len() - 1]), rules.end);
+        for pair in trail.windows(2) {
+            let (i, j) = pair[0];
+            prop_assert!(next_steps(input, i, j, rules).contains(&(pair[1].0 * n_cols + pair[1].1)));
+        }
+        let distinct: HashSet<_> = trail.iter().collect();
+        prop_assert_eq!(distinct.len(), trail.len());
+        Ok(())
+    }
+
+    #[test]
+    fn equal_heights_are_not_unbounded() {
+        let input = vec![vec![0, 1, 2, 3], vec![1, 1, 2, 3], vec![9, 9, 9, 9]];
+        let rules = rules(0, 9, false);
+        let reaches_peak = cells_reaching_peak(&input, &rules);
+        let LongestTrail::Found(trail) = longest_trail(&input, &rules, &reaches_peak) else {
+            panic!("No trail found");
+        };
+        assert_eq!(Some(trail.len() - 1), brute_force_steps(&input, &rules));
+    }
+
+    proptest! {
+        #[test]
+        fn longest_trail_matches_brute_force(
+            input in (1usize..5, 1usize..5).prop_flat_map(|(n_rows, n_cols)| {
+                prop::collection::vec(prop::collection::vec(0i64..10, n_cols), n_rows)
+            }),
+            (min_step, max_step) in prop_oneof![Just((1, 1)), Just((0, 9)), Just((-1, 3))],
+            diagonal in any::<bool>(),
+        ) {
+            let rules = rules(min_step, max_step, diagonal);
+            let reaches_peak = cells_reaching_peak(&input, &rules);
+            match longest_trail(&input, &rules, &reaches_peak) {
+                LongestTrail::NoTrail => prop_assert_eq!(brute_force_steps(&input, &rules), None),
+                LongestTrail::SearchLimit => prop_assert!(false, "Search limit on a tiny map"),
+                LongestTrail::Found(trail) => {
+                    check_trail(&input, &rules, &trail)?;
+                    prop_assert_eq!(Some(trail.len() - 1), brute_force_steps(&input, &rules));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use crate::rules::RuleSet;
+use std::collections::{HashMap, VecDeque};
+
+// Gives up if the stones can take more values than this: the set is probably not
+// closed (e.g. with a rule that keeps adding to the values)
+const MAX_CLOSED_SET: usize = 100_000;
+// Blinks simulated to find the growth rate, unless it settles before
+const MAX_GROWTH_ITERATIONS: usize = 10_000;
+// Largest closed set whose transition matrix is raised to a power directly
+const MAX_MATRIX_SIZE: usize = 100;
+
+// Every value the stones can ever take, starting from the input, and the values
+// each of them turns into after a blink. This is the transition matrix of the
+// stone counts, in sparse form: column k has a 1 in each row of `next[k]`.
+pub struct ClosedSet {
+    pub values: Vec<u64>,
+    next: Vec<(usize, Option<usize>)>,
+    // Number of stones of each value in the input
+    initial: Vec<u64>,
+}
+
+impl ClosedSet {
+    pub fn discover(input: &[u64], rules: &RuleSet) -> ClosedSet {
+        let mut values = vec![];
+        let mut index: HashMap<u64, usize> = HashMap::new();
+        let mut queue = VecDeque::new();
+        let mut id_of = |n: u64, values: &mut Vec<u64>, queue: &mut VecDeque<usize>| {
+            *index.entry(n).or_insert_with(|| {
+                assert!(
+                    values.len() < MAX_CLOSED_SET,
+                    "Stones take more than {} different values",
+                    MAX_CLOSED_SET
+                );
+                values.push(n);
+                queue.push_back(values.len() - 1);
+                values.len() - 1
+            })
+        };
+
+        let mut initial = vec![];
+        for &n in input {
+            let id = id_of(n, &mut values, &mut queue);
+            if initial.len() <= id {
+                initial.resize(id + 1, 0);
+            }
+            initial[id] += 1;
+        }
+
+        let mut next = vec![];
+        while let Some(id) = queue.pop_front() {
+            let (first, second) = rules.apply(values[id]);
+            let first = id_of(first, &mut values, &mut queue);
+            let second = second.map(|second| id_of(second, &mut values, &mut queue));
+            // Values are discovered in order, so this is next[id]
+            next.push((first, second));
+        }
+        initial.resize(values.len(), 0);
+
+        ClosedSet {
+            values,
+            next,
+            initial,
+        }
+    }
+
+    // Number of stones after a blink, given how many there are of each value now
+    fn blink<T: Copy + std::ops::Add<Output = T>>(&self, counts: &[T], zero: T) -> Vec<T> {
+        let mut next_counts = vec![zero; counts.len()];
+        for (&(first, second), &count) in self.next.iter().zip(counts) {
+            next_counts[first] = next_counts[first] + count;
+            if let Some(second) = second {
+                next_counts[second] = next_counts[second] + count;
+            }
+        }
+        next_counts
+    }
+
+    // Factor by which the number of stones grows each blink in the long run.
+    // Simulates the blinks with the proportion of stones of each value, until the
+    // growth in one blink stops changing.
+    pub fn growth_rate(&self) -> f64 {
+        let total: f64 = self.initial.iter().map(|&c| c as f64).sum();
+        let mut proportions: Vec<f64> = self.initial.iter().map(|&c| c as f64 / total).collect();
+        let mut rate = 0.0;
+        for _ in 0..MAX_GROWTH_ITERATIONS {
+            let next = self.blink(&proportions, 0.0);
+            let next_rate: f64 = next.iter().sum();
+            proportions = next.iter().map(|p| p / next_rate).collect();
+            let settled = (next_rate - rate).abs() < 1e-12;
+            rate = next_rate;
+            if settled {
+                break;
+            }
+        }
+        rate
+    }
+
+    // Number of stones after `n_blinks`, modulo the prime `modulus`. Small closed
+    // sets raise the transition matrix to the n-th power directly. Each matrix
+    // product takes size^3 steps, too many for the thousands of values of a
+    // puzzle input, so bigger sets use the recurrence of the totals instead.
+    pub fn count_stones_mod(&self, n_blinks: u64, modulus: u64) -> u64 {
+        assert!(
+            is_prime(modulus),
+            "The modulus must be a prime: {}",
+            modulus
+        );
+        if self.values.len() <= MAX_MATRIX_SIZE {
+            self.count_by_matrix_power(n_blinks, modulus)
+        } else {
+            self.count_by_recurrence(n_blinks, modulus)
+        }
+    }
+
+    // Counts of each value times the transition matrix to the n-th power, by
+    // repeated squaring
+    fn count_by_matrix_power(&self, mut n_blinks: u64, modulus: u64) -> u64 {
+        let size = self.values.len();
+        let zero = Mod::new(0, modulus);
+        let mut power = vec![vec![zero; size]; size];
+        for (k, &(first, second)) in self.next.iter().enumerate() {
+            power[first][k] = power[first][k] + Mod::new(1, modulus);
+            if let Some(second) = second {
+                power[second][k] = power[second][k] + Mod::new(1, modulus);
+            }
+        }
+
+        let mut counts: Vec<Mod> = self.initial.iter().map(|&c| Mod::new(c, modulus)).collect();
+        while n_blinks > 0 {
+            if n_blinks % 2 == 1 {
+                counts = power
+                    .iter()
+                    .map(|row| row.iter().zip(&counts).fold(zero, |t, (&a, &c)| t + a * c))
+                    .collect();
+            }
+            n_blinks /= 2;
+            if n_blinks > 0 {
+                power = square(&power, zero);
+            }
+        }
+        counts.iter().fold(zero, |a, &b| a + b).value
+    }
+
+    // The totals follow a linear recurrence whose order is at most the size of the
+    // closed set (given by the characteristic polynomial of the transition matrix).
+    // The recurrence is found from the first totals with Berlekamp-Massey, and
+    // raising the matrix to the n-th power becomes raising x to the n-th power
+    // modulo the recurrence polynomial (the matrix of that recurrence, in effect).
+    fn count_by_recurrence(&self, n_blinks: u64, modulus: u64) -> u64 {
+        let zero = Mod::new(0, modulus);
+        let mut counts: Vec<Mod> = self.initial.iter().map(|&c| Mod::new(c, modulus)).collect();
+        let mut totals = vec![];
+        for _ in 0..2 * self.values.len() + 2 {
+            totals.push(counts.iter().fold(zero, |a, &b| a + b));
+            counts = self.blink(&counts, zero);
+        }
+
+        if let Some(total) = usize::try_from(n_blinks).ok().and_then(|n| totals.get(n)) {
+            return total.value;
+        }
+        nth_term(&totals, &berlekamp_massey(&totals), n_blinks).value
+    }
+}
+
+fn square(matrix: &[Vec<Mod>], zero: Mod) -> Vec<Vec<Mod>> {
+    let size = matrix.len();
+    let mut squared = vec![vec![zero; size]; size];
+    for (row, squared_row) in matrix.iter().zip(&mut squared) {
+        for (&a, other_row) in row.iter().zip(matrix) {
+            // Early powers are mostly zeros
+            if a.value == 0 {
+                continue;
+            }
+            for (s, &b) in squared_row.iter_mut().zip(other_row) {
+                *s = *s + a * b;
+            }
+        }
+    }
+    squared
+}
+
+// Number modulo a prime
+#[derive(Debug, Clone, Copy)]
+struct Mod {
+    value: u64,
+    modulus: u64,
+}
+
+impl Mod {
+    fn new(value: u64, modulus: u64) -> Mod {
+        Mod {
+            value: value % modulus,
+            modulus,
+        }
+    }
+
+    fn pow(self, mut exp: u64) -> Mod {
+        let mut base = self;
+        let mut result = Mod::new(1, self.modulus);
+        while exp > 0 {
+            if exp % 2 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            exp /= 2;
+        }
+        result
+    }
+
+    fn inverse(self) -> Mod {
+        self.pow(self.modulus - 2)
+    }
+}
+
+impl std::ops::Add for Mod {
+    type Output = Mod;
+    fn add(self, other: Mod) -> Mod {
+        let sum = (self.value as u128 + other.value as u128) % self.modulus as u128;
+        Mod {
+            value: sum as u64,
+            modulus: self.modulus,
+        }
+    }
+}
+
+impl std::ops::Sub for Mod {
+    type Output = Mod;
+    fn sub(self, other: Mod) -> Mod {
+        self + Mod::new(self.modulus - other.value, self.modulus)
+    }
+}
+
+impl std::ops::Mul for Mod {
+    type Output = Mod;
+    fn mul(self, other: Mod) -> Mod {
+        let product = self.value as u128 * other.value as u128 % self.modulus as u128;
+        Mod {
+            value: product as u64,
+            modulus: self.modulus,
+        }
+    }
+}
+
+// Shortest recurrence s[n] = c[0] * s[n - 1] + ... + c[l - 1] * s[n - l] that
+// generates the sequence
+fn berlekamp_massey(s: &[Mod]) -> Vec<Mod> {
+    let modulus = s[0].modulus;
+    let zero = Mod::new(0, modulus);
+    let one = Mod::new(1, modulus);
+
+    // Connection polynomials: the current one, and the one before the last
+    // length change
+    let mut current = vec![one];
+    let mut previous = vec![one];
+    let mut len = 0;
+    let mut shift = 1;
+    let mut previous_discrepancy = one;
+
+    for n in 0..s.len() {
+        let discrepancy = (1..=len).fold(s[n], |d, i| d + current[i] * s[n - i]);
+        if discrepancy.value == 0 {
+            shift += 1;
+            continue;
+        }
+
+        let factor = discrepancy * previous_discrepancy.inverse();
+        let before = current.clone();
+        if current.len() < previous.len() + shift {
+            current.resize(previous.len() + shift, zero);
+        }
+        for (i, &p) in previous.iter().enumerate() {
+            current[i + shift] = current[i + shift] - factor * p;
+        }
+
+        if 2 * len <= n {
+            len = n + 1 - len;
+            previous = before;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+
+    current.resize(len + 1, zero);
+    current[1..].iter().map(|&c| zero - c).collect()
+}
+
+// n-th term of the sequence starting with `s` and following `recurrence`.
+// Computes x^n modulo the recurrence polynomial, x^l - c[0] x^(l-1) - ... - c[l-1],
+// which writes s[n] in terms of s[0..l].
+fn nth_term(s: &[Mod], recurrence: &[Mod], n: u64) -> Mod {
+    let zero = Mod::new(0, s[0].modulus);
+    let l = recurrence.len();
+    if l == 0 {
+        return zero;
+    }
+
+    // Reduces the coefficients from degree `l` up, from the highest one
+    let reduce = |mut poly: Vec<Mod>| {
+        for k in (l..poly.len()).rev() {
+            let coef = poly[k];
+            if coef.value == 0 {
+                continue;
+            }
+            for (i, &c) in recurrence.iter().enumerate() {
+                poly[k - i - 1] = poly[k - i - 1] + coef * c;
+            }
+        }
+        poly.truncate(l);
+        poly
+    };
+
+    let mut power = vec![zero; l];
+    power[0] = Mod::new(1, zero.modulus);
+    for bit in (0..u64::BITS - n.leading_zeros()).rev() {
+        let mut squared = vec![zero; 2 * l];
+        for (i, &a) in power.iter().enumerate() {
+            if a.value == 0 {
+                continue;
+            }
+            for (j, &b) in power.iter().enumerate() {
+                squared[i + j] = squared[i + j] + a * b;
+            }
+        }
+        power = reduce(squared);
+        if (n >> bit) & 1 == 1 {
+            power.insert(0, zero);
+            power = reduce(power);
+        }
+    }
+
+    power
+        .iter()
+        .zip(s)
+        .fold(zero, |total, (&coef, &term)| total + coef * term)
+}
+
+// Deterministic Miller-Rabin, these bases are enough for any u64
+fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    if let Some(&base) = BASES.iter().find(|&&b| n.is_multiple_of(b)) {
+        return n == base;
+    }
+
+    let (mut d, mut r) = (n - 1, 0);
+    while d.is_multiple_of(2) {
+        d /= 2;
+        r += 1;
+    }
+    BASES.iter().all(|&base| {
+        let mut x = Mod::new(base, n).pow(d);
+        if x.value == 1 || x.value == n - 1 {
+            return true;
+        }
+        for _ in 1..r {
+            x = x * x;
+            if x.value == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::count_stones;
+    use num_bigint::BigUint;
+
+    const MODULUS: u64 = 1_000_000_007;
+
+    fn exact_mod(input: &[u64], n_blinks: u64, rules: &RuleSet) -> u64 {
+        let count = count_stones(input, n_blinks, rules) % BigUint::from(MODULUS);
+        u64::try_from(count).unwrap()
+    }
+
+    // Both ways of counting, for blink counts below and above the number of
+    // totals the recurrence is found from
+    #[test]
+    fn modular_counts_match_exact_counts() {
+        let rules = RuleSet::standard();
+        for input in [vec![125, 17], vec![0, 7]] {
+            let closed_set = ClosedSet::discover(&input, &rules);
+            let size = closed_set.values.len() as u64;
+            assert!(closed_set.values.len() <= MAX_MATRIX_SIZE);
+            for n_blinks in [0, 1, 25, 75, 2 * size - 1, 2 * size, 2 * size + 3, 3 * size] {
+                let expected = exact_mod(&input, n_blinks, &rules);
+                assert_eq!(
+                    closed_set.count_stones_mod(n_blinks, MODULUS),
+                    expected,
+                    "{:?} after {} blinks",
+                    input,
+                    n_blinks
+                );
+                assert_eq!(
+                    closed_set.count_by_recurrence(n_blinks, MODULUS),
+                    expected,
+                    "{:?} after {} blinks",
+                    input,
+                    n_blinks
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matrix_power_matches_recurrence_for_many_blinks() {
+        let custom = RuleSet::parse(
+            "value 0 -> replace 1\n\
+             divisible 2 -> split\n\
+             always -> multiply 4",
+        )
+        .unwrap();
+        for (input, rules) in [(vec![125, 17], RuleSet::standard()), (vec![3, 10], custom)] {
+            let closed_set = ClosedSet::discover(&input, &rules);
+            for n_blinks in [1_000, 123_456_789, 1_000_000_000_000_000_000] {
+                assert_eq!(
+                    closed_set.count_by_matrix_power(n_blinks, MODULUS),
+                    closed_set.count_by_recurrence(n_blinks, MODULUS),
+                    "{:?} after {} blinks",
+                    input,
+                    n_blinks
+                );
+            }
+        }
+    }
+}
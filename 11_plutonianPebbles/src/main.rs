@@ -1,21 +1,78 @@
+mod analysis;
+mod rules;
+mod stats;
+
+use analysis::ClosedSet;
+use num_bigint::BigUint;
+use rules::RuleSet;
+use stats::write_stats;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::time::Instant;
 
+// Modulus for the stone counts in the analysis, unless --modulo says otherwise
+const DEFAULT_MODULUS: u64 = 1_000_000_007;
+
+// Number of stones with each value
+type StoneCounts = HashMap<u64, BigUint>;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let input_file = args.get(1).expect("Input file is required");
     let input = parse_input(input_file);
 
+    let get_arg = |name: &str| {
+        args.iter().position(|a| a == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {}", name))
+        })
+    };
+
+    let rules = match get_arg("--rules") {
+        Some(rules_file) => RuleSet::from_file(rules_file),
+        None => RuleSet::standard(),
+    };
+
+    let n_blinks: u64 = match get_arg("--blinks") {
+        Some(n_blinks) => n_blinks.parse().expect("Failed to parse number of blinks"),
+        None if args.contains(&"--part-2".to_string()) => 75,
+        None => 25,
+    };
+
+    if args.contains(&"--analyze".to_string()) {
+        let modulus = get_arg("--modulo").map_or(DEFAULT_MODULUS, |m| {
+            m.parse().expect("Failed to parse modulus")
+        });
+
+        let now = Instant::now();
+        let closed_set = ClosedSet::discover(&input, &rules);
+        println!("Closed set: {} values", closed_set.values.len());
+        println!("Growth rate: {}", closed_set.growth_rate());
+        println!(
+            "Stones after {} blinks (mod {}): {}",
+            n_blinks,
+            modulus,
+            closed_set.count_stones_mod(n_blinks, modulus)
+        );
+        println!("Executed in {:?}", now.elapsed());
+        return;
+    }
+
+    if let Some(stats_file) = get_arg("--stats") {
+        write_stats(&input, n_blinks, &rules, stats_file);
+    }
+
     let now = Instant::now();
-    let result = if args.contains(&"--part-2".to_string()) {
-        part_2(input)
+    let result = if get_arg("--blinks").is_some() {
+        count_stones(&input, n_blinks, &rules)
+    } else if args.contains(&"--part-2".to_string()) {
+        part_2(&input, &rules)
     } else {
-        part_1(input)
+        part_1(&input, &rules)
     };
     let elapsed = now.elapsed();
-    println!("{:?}", result);
+    println!("{}", result);
     println!("Executed in {:?}", elapsed);
 }
 
@@ -27,56 +84,48 @@ fn parse_input(input_file: &str) -> Vec<u64> {
         .collect()
 }
 
-fn part_1(input: Vec<u64>) -> u64 {
-    let mut cache = HashMap::new();
-    input
-        .iter()
-        .map(|n| expand_number(*n, 25, &mut cache))
-        .sum()
+fn part_1(input: &[u64], rules: &RuleSet) -> BigUint {
+    count_stones(input, 25, rules)
 }
 
-fn part_2(input: Vec<u64>) -> u64 {
-    let mut cache = HashMap::new();
-    input
-        .iter()
-        .map(|n| expand_number(*n, 75, &mut cache))
-        .sum()
+fn part_2(input: &[u64], rules: &RuleSet) -> BigUint {
+    count_stones(input, 75, rules)
 }
 
-fn expand_number(n: u64, height: usize, cache: &mut HashMap<(u64, usize), u64>) -> u64 {
-    if height == 0 {
-        return 1;
+// The order of the stones doesn't matter for counting them, and stones with the same
+// value always turn into the same stones. So it is enough to keep how many stones
+// there are of each value, and update that once per blink.
+fn count_stones(input: &[u64], n_blinks: u64, rules: &RuleSet) -> BigUint {
+    let mut stones = stone_counts(input);
+    for _ in 0..n_blinks {
+        stones = blink(&stones, rules);
     }
+    stones.values().sum()
+}
 
-    if let Some(cached) = cache.get(&(n, height)) {
-        return *cached;
+fn stone_counts(input: &[u64]) -> StoneCounts {
+    let mut stones = StoneCounts::new();
+    for &n in input {
+        *stones.entry(n).or_default() += 1u32;
     }
+    stones
+}
 
-    let result = if height == 0 {
-        1
-    } else {
-        if n == 0 {
-            expand_number(1, height - 1, cache)
-        } else {
-            let n_digits = get_n_digits(n);
-            if n_digits % 2 == 0 {
-                let div = u64::pow(10, (n_digits / 2).try_into().unwrap());
-                let left = n / div;
-                let right = n % div;
-                expand_number(left, height - 1, cache)
-                    + expand_number(right, height - 1, cache)
-            } else {
-                expand_number(n * 2024, height - 1, cache)
-            }
+fn blink(stones: &StoneCounts, rules: &RuleSet) -> StoneCounts {
+    let mut next = StoneCounts::with_capacity(stones.len());
+    for (&n, count) in stones {
+        let (first, second) = rules.apply(n);
+        *next.entry(first).or_default() += count;
+        if let Some(second) = second {
+            *next.entry(second).or_default() += count;
         }
-    };
-    cache.insert((n, height), result);
-    result
+    }
+    next
 }
 
 fn get_n_digits(mut n: u64) -> u8 {
-    let mut n_digits = 0;
-    while n >= 1 {
+    let mut n_digits = 1;
+    while n >= 10 {
         n /= 10;
         n_digits += 1;
     }
@@ -0,0 +1,225 @@
+use crate::get_n_digits;
+use std::fs;
+
+// Rules describing what a stone turns into when we blink. They are read from a
+// file with one rule per line, `<condition> -> <action>`, tried in order: the
+// first rule whose condition holds is applied. A stone no rule applies to stays
+// the same. Empty lines and lines starting with '#' are ignored.
+//
+// Conditions:
+// - `value N`: the stone is engraved with N
+// - `digits even` / `digits odd`: parity of the number of digits
+// - `divisible N`: the number is a multiple of N
+// - `always`
+// Actions:
+// - `replace N`: the stone is now engraved with N
+// - `split`: the stone splits in two, the left and right halves of its digits.
+//   With an odd number of digits the left stone gets the extra digit.
+// - `multiply N` / `add N`
+//
+// The puzzle rules are:
+//     value 0 -> replace 1
+//     digits even -> split
+//     always -> multiply 2024
+pub struct RuleSet(Vec<Rule>);
+
+struct Rule {
+    condition: Condition,
+    action: Action,
+}
+
+enum Condition {
+    Value(u64),
+    EvenDigits,
+    OddDigits,
+    Divisible(u64),
+    Always,
+}
+
+enum Action {
+    Replace(u64),
+    Split,
+    Multiply(u64),
+    Add(u64),
+}
+
+impl RuleSet {
+    pub fn standard() -> RuleSet {
+        RuleSet(vec![
+            Rule {
+                condition: Condition::Value(0),
+                action: Action::Replace(1),
+            },
+            Rule {
+                condition: Condition::EvenDigits,
+                action: Action::Split,
+            },
+            Rule {
+                condition: Condition::Always,
+                action: Action::Multiply(2024),
+            },
+        ])
+    }
+
+    pub fn from_file(rules_file: &str) -> RuleSet {
+        let content = fs::read_to_string(rules_file).expect("Failed to read rules file");
+        RuleSet::parse(&content).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn parse(content: &str) -> Result<RuleSet, String> {
+        let rules = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+            .map(|(l, line)| {
+                parse_rule(line).map_err(|e| format!("Invalid rule at line {}: {}", l + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(RuleSet(rules))
+    }
+
+    // What a stone turns into after one blink: one stone, or two if it splits
+    pub fn apply(&self, n: u64) -> (u64, Option<u64>) {
+        let Some(rule) = self.0.iter().find(|rule| rule.condition.holds(n)) else {
+            return (n, None);
+        };
+
+        match rule.action {
+            Action::Replace(value) => (value, None),
+            Action::Split => {
+                let div = u64::pow(10, u32::from(get_n_digits(n) / 2));
+                (n / div, Some(n % div))
+            }
+            Action::Multiply(factor) => {
+                let product = n.checked_mul(factor).expect("Stone value overflow");
+                (product, None)
+            }
+            Action::Add(term) => {
+                let sum = n.checked_add(term).expect("Stone value overflow");
+                (sum, None)
+            }
+        }
+    }
+}
+
+impl Condition {
+    fn holds(&self, n: u64) -> bool {
+        match *self {
+            Condition::Value(value) => n == value,
+            Condition::EvenDigits => get_n_digits(n).is_multiple_of(2),
+            Condition::OddDigits => !get_n_digits(n).is_multiple_of(2),
+            Condition::Divisible(d) => n.is_multiple_of(d),
+            Condition::Always => true,
+        }
+    }
+}
+
+fn parse_rule(line: &str) -> Result<Rule, String> {
+    let (condition, action) = line
+        .split_once("->")
+        .ok_or_else(|| "expected `<condition> -> <action>`".to_string())?;
+    Ok(Rule {
+        condition: parse_condition(condition)?,
+        action: parse_action(action)?,
+    })
+}
+
+fn parse_condition(condition: &str) -> Result<Condition, String> {
+    let words: Vec<&str> = condition.split_whitespace().collect();
+    match words.as_slice() {
+        ["value", n] => Ok(Condition::Value(parse_number(n)?)),
+        ["digits", "even"] => Ok(Condition::EvenDigits),
+        ["digits", "odd"] => Ok(Condition::OddDigits),
+        ["divisible", n] => match parse_number(n)? {
+            0 => Err("can't be divisible by 0".to_string()),
+            n => Ok(Condition::Divisible(n)),
+        },
+        ["always"] => Ok(Condition::Always),
+        _ => Err(format!("unknown condition `{}`", condition.trim())),
+    }
+}
+
+fn parse_action(action: &str) -> Result<Action, String> {
+    let words: Vec<&str> = action.split_whitespace().collect();
+    match words.as_slice() {
+        ["replace", n] => Ok(Action::Replace(parse_number(n)?)),
+        ["split"] => Ok(Action::Split),
+        ["multiply", n] => Ok(Action::Multiply(parse_number(n)?)),
+        ["add", n] => Ok(Action::Add(parse_number(n)?)),
+        _ => Err(format!("unknown action `{}`", action.trim())),
+    }
+}
+
+fn parse_number(n: &str) -> Result<u64, String> {
+    n.parse().map_err(|_| format!("invalid number `{}`", n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_puzzle_rules() {
+        let rules = RuleSet::parse(
+            "# The puzzle\n\
+             value 0 -> replace 1\n\
+             \n\
+             digits even -> split\n\
+             always -> multiply 2024\n",
+        )
+        .unwrap();
+        for n in [0, 1, 10, 99, 1000, 125, 253000] {
+            assert_eq!(rules.apply(n), RuleSet::standard().apply(n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn parses_every_condition_and_action() {
+        let rules = RuleSet::parse(
+            "value 7 -> replace 70\n\
+             divisible 5 -> add 3\n\
+             digits odd -> split\n\
+             digits even -> multiply 3\n",
+        )
+        .unwrap();
+        assert_eq!(rules.apply(7), (70, None));
+        assert_eq!(rules.apply(25), (28, None));
+        // The left stone gets the extra digit
+        assert_eq!(rules.apply(123), (12, Some(3)));
+        assert_eq!(rules.apply(12), (36, None));
+        // With no rule left, stones stay the same
+        assert_eq!(
+            RuleSet::parse("value 1 -> add 1").unwrap().apply(5),
+            (5, None)
+        );
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let error = |content: &str| RuleSet::parse(content).err().unwrap();
+        assert_eq!(
+            error("always -> split\ndivisible 0 -> add 1"),
+            "Invalid rule at line 2: can't be divisible by 0"
+        );
+        assert_eq!(
+            error("value 0 replace 1"),
+            "Invalid rule at line 1: expected `<condition> -> <action>`"
+        );
+        assert_eq!(
+            error("# comment\n\nvalue x -> replace 1"),
+            "Invalid rule at line 3: invalid number `x`"
+        );
+        assert_eq!(
+            error("digits many -> split"),
+            "Invalid rule at line 1: unknown condition `digits many`"
+        );
+        assert_eq!(
+            error("always -> divide 2"),
+            "Invalid rule at line 1: unknown action `divide 2`"
+        );
+        assert_eq!(
+            error("value -1 -> replace 1"),
+            "Invalid rule at line 1: invalid number `-1`"
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use crate::rules::RuleSet;
+use crate::{blink, get_n_digits, stone_counts, StoneCounts};
+use num_bigint::BigUint;
+use std::fs;
+
+// Values listed in the `most_frequent` column of each row
+const TOP_VALUES: usize = 3;
+// A u64 has at most 20 digits
+const MAX_DIGITS: usize = 20;
+
+// Writes a CSV with a row for the stones before the first blink and after each
+// blink. Columns:
+// - total: number of stones
+// - distinct: number of different values
+// - most_frequent: the most common values with their counts, as `value:count`
+//   separated by ';'
+// - digits_1 to digits_20: number of stones with that many digits
+pub fn write_stats(input: &[u64], n_blinks: u64, rules: &RuleSet, stats_file: &str) {
+    let digit_columns: Vec<String> = (1..=MAX_DIGITS).map(|d| format!("digits_{}", d)).collect();
+    let mut csv = format!(
+        "blink,total,distinct,most_frequent,{}\n",
+        digit_columns.join(",")
+    );
+
+    let mut stones = stone_counts(input);
+    for b in 0..=n_blinks {
+        if b > 0 {
+            stones = blink(&stones, rules);
+        }
+        csv.push_str(&format!("{},{}\n", b, stats_row(&stones)));
+    }
+
+    fs::write(stats_file, csv).expect("Failed to write stats file");
+}
+
+fn stats_row(stones: &StoneCounts) -> String {
+    let total: BigUint = stones.values().sum();
+
+    let mut by_count: Vec<(&u64, &BigUint)> = stones.iter().collect();
+    by_count.sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then(n1.cmp(n2)));
+    let most_frequent: Vec<String> = by_count
+        .iter()
+        .take(TOP_VALUES)
+        .map(|(n, count)| format!("{}:{}", n, count))
+        .collect();
+
+    let mut digit_counts = vec![BigUint::ZERO; MAX_DIGITS];
+    for (&n, count) in stones {
+        digit_counts[get_n_digits(n) as usize - 1] += count;
+    }
+    let digit_counts: Vec<String> = digit_counts.iter().map(|c| c.to_string()).collect();
+
+    format!(
+        "{},{},{},{}",
+        total,
+        stones.len(),
+        most_frequent.join(";"),
+        digit_counts.join(",")
+    )
+}
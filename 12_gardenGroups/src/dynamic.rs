@@ -0,0 +1,244 @@
+use crate::regions::{plot_fences, region_stats, Labels};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const STRAIGHT: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIAGONAL: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+// What a region costs to fence
+#[derive(Debug)]
+struct Price {
+    area: u64,
+    perimeter: u64,
+    sides: u64,
+}
+
+// Garden whose plots can be replanted one at a time, keeping the total prices up
+// to date. Replanting a plot only changes the regions around it: the region it
+// was in (which may split) and the ones it touches (which may merge with it, or
+// get new fences). Those are the only regions labelled and priced again.
+pub struct DynamicGarden {
+    garden: Vec<Vec<char>>,
+    diagonal: bool,
+    // Region of each plot. Regions get a new id every time they change, so ids
+    // are never reused.
+    labels: Vec<Vec<usize>>,
+    prices: HashMap<usize, Price>,
+    next_id: usize,
+    part_1: u64,
+    part_2: u64,
+}
+
+impl DynamicGarden {
+    pub fn load(garden: Vec<Vec<char>>, diagonal: bool) -> DynamicGarden {
+        let labels = Labels::new(&garden, diagonal);
+        let regions = region_stats(&garden, &labels);
+
+        let mut prices = HashMap::new();
+        for (id, region) in regions.iter().enumerate() {
+            let price = Price {
+                area: region.area,
+                perimeter: region.perimeter,
+                sides: region.sides,
+            };
+            prices.insert(id, price);
+        }
+
+        DynamicGarden {
+            labels: (0..labels.n_rows)
+                .map(|i| (0..labels.n_cols).map(|j| labels.get(i, j)).collect())
+                .collect(),
+            diagonal,
+            next_id: regions.len(),
+            part_1: regions.iter().map(|r| r.area * r.perimeter).sum(),
+            part_2: regions.iter().map(|r| r.area * r.sides).sum(),
+            prices,
+            garden,
+        }
+    }
+
+    pub fn part_1(&self) -> u64 {
+        self.part_1
+    }
+
+    pub fn part_2(&self) -> u64 {
+        self.part_2
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, plant: char) {
+        assert!(
+            i < self.garden.len() && j < self.garden[i].len(),
+            "Plot ({}, {}) is outside the garden",
+            i,
+            j
+        );
+        if self.garden[i][j] == plant {
+            return;
+        }
+
+        // Regions the plot is in or touches, even at a corner (their corners
+        // there may change)
+        let mut affected = vec![(i, j)];
+        for (di, dj) in STRAIGHT.iter().chain(&DIAGONAL) {
+            if let Some(plot) = self.plot_at(i as isize + di, j as isize + dj) {
+                affected.push(plot);
+            }
+        }
+        let mut plots = vec![];
+        let mut seen_ids = HashSet::new();
+        for (ai, aj) in affected {
+            let id = self.labels[ai][aj];
+            if seen_ids.insert(id) {
+                let price = self.prices.remove(&id).expect("Unknown region");
+                self.part_1 -= price.area * price.perimeter;
+                self.part_2 -= price.area * price.sides;
+                plots.extend(self.flood_fill(ai, aj, |(i, j)| self.labels[i][j] == id));
+            }
+        }
+
+        self.garden[i][j] = plant;
+
+        // Label the plots of the affected regions again. Plots next to them that
+        // weren't affected have other plants, so new regions never go beyond them.
+        let mut relabelled = HashSet::new();
+        for &(pi, pj) in &plots {
+            if relabelled.contains(&(pi, pj)) {
+                continue;
+            }
+            let region_plant = self.garden[pi][pj];
+            let region = self.flood_fill(pi, pj, |(i, j)| self.garden[i][j] == region_plant);
+
+            let id = self.next_id;
+            self.next_id += 1;
+            for &(ri, rj) in &region {
+                self.labels[ri][rj] = id;
+                relabelled.insert((ri, rj));
+            }
+            self.add_region(id, &region);
+        }
+    }
+
+    fn add_region(&mut self, id: usize, region: &[(usize, usize)]) {
+        let mut perimeter = 0;
+        let mut sides = 0;
+        for &(i, j) in region {
+            let fences = plot_fences(i, j, |i, j| {
+                self.plot_at(i, j)
+                    .is_some_and(|(i, j)| self.labels[i][j] == id)
+            });
+            perimeter += fences.perimeter;
+            sides += fences.sides();
+        }
+
+        let area = region.len() as u64;
+        self.part_1 += area * perimeter;
+        self.part_2 += area * sides;
+        self.prices.insert(
+            id,
+            Price {
+                area,
+                perimeter,
+                sides,
+            },
+        );
+    }
+
+    fn plot_at(&self, i: isize, j: isize) -> Option<(usize, usize)> {
+        if i < 0 || j < 0 || i as usize >= self.garden.len() {
+            return None;
+        }
+        let (i, j) = (i as usize, j as usize);
+        (j < self.garden[i].len()).then_some((i, j))
+    }
+
+    // Plots connected to (i, j) through plots that are `together` with it
+    fn flood_fill(
+        &self,
+        i: usize,
+        j: usize,
+        together: impl Fn((usize, usize)) -> bool,
+    ) -> Vec<(usize, usize)> {
+        let directions = if self.diagonal {
+            [STRAIGHT, DIAGONAL].concat()
+        } else {
+            STRAIGHT.to_vec()
+        };
+
+        let mut region = vec![(i, j)];
+        let mut seen = HashSet::from([(i, j)]);
+        let mut queue = VecDeque::from([(i, j)]);
+        while let Some((pi, pj)) = queue.pop_front() {
+            for &(di, dj) in &directions {
+                let Some(next) = self.plot_at(pi as isize + di, pj as isize + dj) else {
+                    continue;
+                };
+                if together(next) && seen.insert(next) {
+                    region.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        region
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const PLANTS: [char; 3] = ['A', 'B', 'C'];
+
+    // Plot and its new plant
+    type Edit = (usize, usize, char);
+
+    fn full_prices(garden: &[Vec<char>], diagonal: bool) -> (u64, u64) {
+        let regions = region_stats(garden, &Labels::new(garden, diagonal));
+        (
+            regions.iter().map(|r| r.area * r.perimeter).sum(),
+            regions.iter().map(|r| r.area * r.sides).sum(),
+        )
+    }
+
+    // A garden of two plants, and edits that can bring in a third one
+    fn garden_and_edits() -> impl Strategy<Value = (Vec<Vec<char>>, Vec<Edit>)> {
+        (1usize..12, 1usize..12).prop_flat_map(|(n_rows, n_cols)| {
+            let plant = |n| proptest::sample::select(PLANTS[..n].to_vec());
+            let garden = prop::collection::vec(prop::collection::vec(plant(2), n_cols), n_rows);
+            let edits = prop::collection::vec((0..n_rows, 0..n_cols, plant(3)), 0..200);
+            (garden, edits)
+        })
+    }
+
+    fn check_edits(
+        mut garden: Vec<Vec<char>>,
+        edits: Vec<Edit>,
+        diagonal: bool,
+    ) -> Result<(), TestCaseError> {
+        let mut dynamic = DynamicGarden::load(garden.clone(), diagonal);
+        for (i, j, plant) in edits {
+            dynamic.set(i, j, plant);
+            garden[i][j] = plant;
+            prop_assert_eq!(
+                (dynamic.part_1(), dynamic.part_2()),
+                full_prices(&garden, diagonal),
+                "Prices differ after setting ({}, {}) to {}",
+                i,
+                j,
+                plant
+            );
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn edits_match_full_recomputation((garden, edits) in garden_and_edits()) {
+            check_edits(garden, edits, false)?;
+        }
+
+        #[test]
+        fn diagonal_edits_match_full_recomputation((garden, edits) in garden_and_edits()) {
+            check_edits(garden, edits, true)?;
+        }
+    }
+}
@@ -1,4 +1,10 @@
-use std::collections::HashSet;
+mod dynamic;
+mod outline;
+mod regions;
+
+use dynamic::DynamicGarden;
+use outline::write_svg;
+use regions::{region_stats, Labels, Region};
 use std::env;
 use std::fs;
 use std::time::Instant;
@@ -8,11 +14,48 @@ fn main() {
     let input_file = args.get(1).expect("Input file is required");
     let input = parse_input(input_file);
 
+    let get_arg = |name: &str| {
+        args.iter().position(|a| a == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {}", name))
+        })
+    };
+
+    let diagonal = args.contains(&"--diagonal".to_string());
+
+    if get_arg("--stats").is_some() || get_arg("--svg").is_some() {
+        let labels = Labels::new(&input, diagonal);
+        let regions = region_stats(&input, &labels);
+        if let Some(stats_file) = get_arg("--stats") {
+            write_stats(&regions, stats_file);
+        }
+        if let Some(svg_file) = get_arg("--svg") {
+            write_svg(&labels, &regions, svg_file);
+        }
+    }
+
+    if let Some(edits_file) = get_arg("--edits") {
+        let mut garden = DynamicGarden::load(input, diagonal);
+        println!("i\tj\tplant\tpart_1\tpart_2");
+        for (i, j, plant) in parse_edits(edits_file) {
+            garden.set(i, j, plant);
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                i,
+                j,
+                plant,
+                garden.part_1(),
+                garden.part_2()
+            );
+        }
+        return;
+    }
+
     let now = Instant::now();
     let result = if args.contains(&"--part-2".to_string()) {
-        part_2(input)
+        part_2(&input, diagonal)
     } else {
-        part_1(input)
+        part_1(&input, diagonal)
     };
     let elapsed = now.elapsed();
     println!("{:?}", result);
@@ -23,133 +66,54 @@ fn parse_input(input_file: &str) -> Vec<Vec<char>> {
     fs::read_to_string(input_file)
         .expect("Failed to read input file")
         .lines()
-        .map(|l| l.chars().collect())
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.trim_end().chars().collect())
         .collect()
 }
 
-fn part_1(input: Vec<Vec<char>>) -> u32 {
-    let mut visited = HashSet::new();
-    input
-        .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            row.iter()
-                .enumerate()
-                .map(|(j, _)| {
-                    // I could use the get_area_and_register_edges function, but I don't really
-                    // need to register all the edges
-                    let (area, perimeter) =
-                        get_area_and_perimeter(&input, i as isize, j as isize, &mut visited);
-                    area * perimeter
-                })
-                .sum::<u32>()
+// One edit per line: the plot's row, column and new plant, like `3 4 B`
+fn parse_edits(edits_file: &str) -> Vec<(usize, usize, char)> {
+    fs::read_to_string(edits_file)
+        .expect("Failed to read edits file")
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let parts: Vec<&str> = l.split_whitespace().collect();
+            let [i, j, plant] = parts[..] else {
+                panic!("Invalid edit: {}", l);
+            };
+            let mut plant = plant.chars();
+            match (i.parse(), j.parse(), plant.next(), plant.next()) {
+                (Ok(i), Ok(j), Some(plant), None) => (i, j, plant),
+                _ => panic!("Invalid edit: {}", l),
+            }
         })
-        .sum()
-}
-
-fn get_area_and_perimeter(
-    input: &Vec<Vec<char>>,
-    i: isize,
-    j: isize,
-    visited: &mut HashSet<(isize, isize)>,
-) -> (u32, u32) {
-    if visited.contains(&(i, j)) {
-        return (0, 0);
-    }
-    visited.insert((i, j));
-
-    let mut area = 1;
-    let mut perimeter = 0;
-
-    for (di, dj) in [(0, 1), (1, 0), (0, -1), (-1, 0)] {
-        let (new_i, new_j) = (i + di, j + dj);
-        if new_i < 0
-            || new_j < 0
-            || new_i >= input.len() as isize
-            || new_j >= input[0].len() as isize
-            || input[new_i as usize][new_j as usize] != input[i as usize][j as usize]
-        {
-            perimeter += 1;
-        } else {
-            let (new_area, new_perimeter) = get_area_and_perimeter(input, new_i, new_j, visited);
-            area += new_area;
-            perimeter += new_perimeter;
-        }
-    }
-    (area, perimeter)
+        .collect()
 }
 
-fn part_2(input: Vec<Vec<char>>) -> u32 {
-    let mut visited = HashSet::new();
-    input
+fn part_1(input: &[Vec<char>], diagonal: bool) -> u64 {
+    let labels = Labels::new(input, diagonal);
+    region_stats(input, &labels)
         .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            row.iter()
-                .enumerate()
-                .map(|(j, _)| {
-                    let mut edges = HashSet::new();
-                    let area = get_area_and_register_edges(
-                        &input,
-                        i as isize,
-                        j as isize,
-                        &mut visited,
-                        &mut edges,
-                    );
-                    let n_sides = count_sides(&mut edges);
-                    area * n_sides as u32
-                })
-                .sum::<u32>()
-        })
+        .map(|r| r.area * r.perimeter)
         .sum()
 }
 
-fn get_area_and_register_edges(
-    input: &Vec<Vec<char>>,
-    i: isize,
-    j: isize,
-    visited: &mut HashSet<(isize, isize)>,
-    edges: &mut HashSet<(isize, isize, isize, isize)>,
-) -> u32 {
-    if visited.contains(&(i, j)) {
-        return 0;
-    }
-    visited.insert((i, j));
-
-    let mut area = 1;
-
-    for (di, dj) in [(0, 1), (1, 0), (0, -1), (-1, 0)] {
-        let (new_i, new_j) = (i + di, j + dj);
-        if new_i < 0
-            || new_j < 0
-            || new_i >= input.len() as isize
-            || new_j >= input[0].len() as isize
-            || input[new_i as usize][new_j as usize] != input[i as usize][j as usize]
-        {
-            edges.insert((i, j, di, dj));
-        } else {
-            area += get_area_and_register_edges(input, new_i, new_j, visited, edges);
-        }
-    }
-    area
+fn part_2(input: &[Vec<char>], diagonal: bool) -> u64 {
+    let labels = Labels::new(input, diagonal);
+    region_stats(input, &labels)
+        .iter()
+        .map(|r| r.area * r.sides)
+        .sum()
 }
 
-fn count_sides(edges: &mut HashSet<(isize, isize, isize, isize)>) -> u32 {
-    let mut sides = edges.clone();
-
-    for edge in edges.iter() {
-        let (i, j, di, dj) = *edge;
-        if !sides.contains(&(i, j, di, dj)) {
-            continue;
-        }
-        // Remove all adjacent edges with the same direction
-        for (ni, nj) in [(dj, di), (-dj, -di)] {
-            let (mut new_i, mut new_j) = (i + ni, j + nj);
-            while sides.remove(&(new_i, new_j, di, dj)) {
-                new_i += ni;
-                new_j += nj;
-            }
-        }
+fn write_stats(regions: &[Region], stats_file: &str) {
+    let mut csv = "plant,area,perimeter,sides,min_i,min_j,max_i,max_j,holes\n".to_string();
+    for r in regions {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            r.plant, r.area, r.perimeter, r.sides, r.min_i, r.min_j, r.max_i, r.max_j, r.holes
+        ));
     }
-    sides.len() as u32
+    fs::write(stats_file, csv).expect("Failed to write stats file");
 }
@@ -0,0 +1,177 @@
+use crate::regions::{Labels, Region};
+use std::fs;
+
+// Side of the square each plot takes in the SVG
+const SVG_CELL_SIZE: usize = 12;
+
+// Point at the corner of plots, as (row, column). The top left corner of plot
+// (i, j) is (i, j) and its bottom right corner is (i + 1, j + 1).
+pub type Vertex = (usize, usize);
+
+// Closed polygon, as the list of its corners
+pub type Ring = Vec<Vertex>;
+
+// Headings along a fence, in clockwise order
+const HEADINGS: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+const NORTH: usize = 0;
+const EAST: usize = 1;
+const SOUTH: usize = 2;
+const WEST: usize = 3;
+
+// Outline of each region: the outer ring first, then one ring per hole.
+// Fences are walked clockwise around the plots, with the region on the right.
+// Where two plots of the region only touch at a corner, the walk goes through
+// that corner (so areas touching each other only at that corner are separate
+// holes). Every corner of a ring is where a side ends, so the rings of a region
+// have as many corners as the region has sides.
+pub fn region_outlines(labels: &Labels) -> Vec<Vec<Ring>> {
+    let mut outlines: Vec<Vec<Ring>> = vec![vec![]; labels.n_regions];
+    let mut visited = vec![false; labels.n_rows * labels.n_cols * 4];
+
+    // The first fence found for a region is the top of its first plot, which
+    // is on the outer ring
+    for i in 0..labels.n_rows {
+        for j in 0..labels.n_cols {
+            let label = labels.get(i, j);
+            for heading in [EAST, SOUTH, WEST, NORTH] {
+                let start = fence_start((i, j), heading);
+                let Some(fence) = fence(labels, label, start, heading) else {
+                    continue;
+                };
+                if !visited[fence] {
+                    outlines[label].push(trace_ring(labels, label, start, heading, &mut visited));
+                }
+            }
+        }
+    }
+    outlines
+}
+
+// Corner a fence of plot (i, j) starts at, walking clockwise around the plot
+fn fence_start((i, j): Vertex, heading: usize) -> Vertex {
+    match heading {
+        EAST => (i, j),
+        SOUTH => (i, j + 1),
+        WEST => (i + 1, j + 1),
+        _ => (i + 1, j),
+    }
+}
+
+// Fence of the region starting at `vertex` and going towards `heading`, as an
+// index into the `visited` flags. It's the top, right, bottom or left side of a
+// plot of the region, when the plot on the other side isn't in it.
+fn fence(labels: &Labels, label: usize, vertex: Vertex, heading: usize) -> Option<usize> {
+    let (r, c) = (vertex.0 as isize, vertex.1 as isize);
+    let (inside, outside) = match heading {
+        EAST => ((r, c), (r - 1, c)),
+        SOUTH => ((r, c - 1), (r, c)),
+        WEST => ((r - 1, c - 1), (r, c - 1)),
+        _ => ((r - 1, c), (r - 1, c - 1)),
+    };
+    if labels.at(inside.0, inside.1) != Some(label)
+        || labels.at(outside.0, outside.1) == Some(label)
+    {
+        return None;
+    }
+    Some((inside.0 as usize * labels.n_cols + inside.1 as usize) * 4 + heading)
+}
+
+fn trace_ring(
+    labels: &Labels,
+    label: usize,
+    start: Vertex,
+    start_heading: usize,
+    visited: &mut [bool],
+) -> Ring {
+    let mut ring = vec![];
+    let (mut vertex, mut heading) = (start, start_heading);
+    loop {
+        let fence_id = fence(labels, label, vertex, heading).expect("Ring is broken");
+        visited[fence_id] = true;
+
+        let (di, dj) = HEADINGS[heading];
+        let end = (
+            (vertex.0 as isize + di) as usize,
+            (vertex.1 as isize + dj) as usize,
+        );
+        // Left first, so that the ring goes through corners where the region
+        // touches itself diagonally
+        let next_heading = [3, 0, 1]
+            .iter()
+            .map(|turn| (heading + turn) % 4)
+            .find(|&h| fence(labels, label, end, h).is_some())
+            .expect("Ring is broken");
+        if next_heading != heading {
+            ring.push(end);
+        }
+
+        (vertex, heading) = (end, next_heading);
+        if (vertex, heading) == (start, start_heading) {
+            return ring;
+        }
+    }
+}
+
+// Draws every region with its holes, coloured by plant, with its plant and number
+// of sides written on its first plot
+pub fn write_svg(labels: &Labels, regions: &[Region], svg_file: &str) {
+    let outlines = region_outlines(labels);
+    let (width, height) = (labels.n_cols * SVG_CELL_SIZE, labels.n_rows * SVG_CELL_SIZE);
+
+    let mut plants: Vec<char> = regions.iter().map(|r| r.plant).collect();
+    plants.sort();
+    plants.dedup();
+    let hue = |plant: char| {
+        let k = plants.binary_search(&plant).expect("Unknown plant");
+        k as f64 / plants.len() as f64 * 360.0
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+        width, height, width, height
+    );
+    for (label, (region, rings)) in regions.iter().zip(&outlines).enumerate() {
+        let corners: usize = rings.iter().map(|ring| ring.len()).sum();
+        assert_eq!(
+            corners as u64, region.sides,
+            "Outline of the {} region at ({}, {}) doesn't match its sides",
+            region.plant, region.min_i, region.min_j
+        );
+
+        let path: Vec<String> = rings
+            .iter()
+            .map(|ring| {
+                let points: Vec<String> = ring
+                    .iter()
+                    .map(|&(r, c)| format!("{} {}", c * SVG_CELL_SIZE, r * SVG_CELL_SIZE))
+                    .collect();
+                format!("M {} Z", points.join(" L "))
+            })
+            .collect();
+        svg.push_str(&format!(
+            "<path d=\"{}\" fill=\"hsl({:.0}, 70%, 65%)\" fill-rule=\"evenodd\" stroke=\"black\"><title>{}: area {}, {} sides, {} holes</title></path>\n",
+            path.join(" "),
+            hue(region.plant),
+            region.plant,
+            region.area,
+            region.sides,
+            region.holes
+        ));
+
+        // The first plot of a region is the leftmost one of its top row
+        let first_j = (region.min_j..=region.max_j)
+            .find(|&j| labels.get(region.min_i, j) == label)
+            .expect("Region has no plots in its top row");
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}:{}</text>\n",
+            first_j * SVG_CELL_SIZE + SVG_CELL_SIZE / 2,
+            region.min_i * SVG_CELL_SIZE + SVG_CELL_SIZE / 2,
+            SVG_CELL_SIZE / 2,
+            region.plant,
+            region.sides
+        ));
+    }
+    svg.push_str("</svg>\n");
+
+    fs::write(svg_file, svg).expect("Failed to write SVG");
+}
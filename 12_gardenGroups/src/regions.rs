@@ -0,0 +1,227 @@
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIAGONALS: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+#[derive(Debug)]
+pub struct Region {
+    pub plant: char,
+    pub area: u64,
+    pub perimeter: u64,
+    pub sides: u64,
+    // Bounding box, as the first and last row and column of the region
+    pub min_i: usize,
+    pub min_j: usize,
+    pub max_i: usize,
+    pub max_j: usize,
+    // Number of areas inside the region that don't belong to it
+    pub holes: u64,
+}
+
+// Region of every plot in the garden. Regions are numbered in the order of their
+// first plot, going row by row.
+pub struct Labels {
+    pub n_rows: usize,
+    pub n_cols: usize,
+    pub n_regions: usize,
+    labels: Vec<usize>,
+}
+
+impl Labels {
+    // Plots with the same plant next to each other are merged with a union-find,
+    // so this doesn't need recursion (or any stack) however big the regions are.
+    // With `diagonal`, plots touching at a corner are next to each other too.
+    pub fn new(garden: &[Vec<char>], diagonal: bool) -> Labels {
+        let n_rows = garden.len();
+        let n_cols = garden.first().map_or(0, |row| row.len());
+        assert!(
+            garden.iter().all(|row| row.len() == n_cols),
+            "All rows must have the same length"
+        );
+
+        let mut union_find = UnionFind::new(n_rows * n_cols);
+        for i in 0..n_rows {
+            for j in 0..n_cols {
+                if j + 1 < n_cols && garden[i][j + 1] == garden[i][j] {
+                    union_find.union(i * n_cols + j, i * n_cols + j + 1);
+                }
+                if i + 1 < n_rows && garden[i + 1][j] == garden[i][j] {
+                    union_find.union(i * n_cols + j, (i + 1) * n_cols + j);
+                }
+                if diagonal && i + 1 < n_rows {
+                    if j + 1 < n_cols && garden[i + 1][j + 1] == garden[i][j] {
+                        union_find.union(i * n_cols + j, (i + 1) * n_cols + j + 1);
+                    }
+                    if j > 0 && garden[i + 1][j - 1] == garden[i][j] {
+                        union_find.union(i * n_cols + j, (i + 1) * n_cols + j - 1);
+                    }
+                }
+            }
+        }
+
+        let mut region_of_root = vec![usize::MAX; n_rows * n_cols];
+        let mut n_regions = 0;
+        let labels = (0..n_rows * n_cols)
+            .map(|cell| {
+                let root = union_find.find(cell);
+                if region_of_root[root] == usize::MAX {
+                    region_of_root[root] = n_regions;
+                    n_regions += 1;
+                }
+                region_of_root[root]
+            })
+            .collect();
+
+        Labels {
+            n_rows,
+            n_cols,
+            n_regions,
+            labels,
+        }
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> usize {
+        self.labels[i * self.n_cols + j]
+    }
+
+    // None outside the garden
+    pub fn at(&self, i: isize, j: isize) -> Option<usize> {
+        if i < 0 || j < 0 || i as usize >= self.n_rows || j as usize >= self.n_cols {
+            return None;
+        }
+        Some(self.get(i as usize, j as usize))
+    }
+}
+
+// Sides are counted through corners: a fence turns at each end of a side, so a
+// region has as many sides as corners. Looking at one corner of a plot, with the
+// two plots next to it along the sides and the one diagonally across:
+// - if neither plot along the sides is in the region, the fence turns outwards
+//   (a convex corner)
+// - if both are but the one across isn't, the fence turns inwards (concave)
+// Plots of a region that only touch at a corner (a pinch) have a convex corner
+// each there, as the fence goes through that point twice, turning each time.
+// Corners also give the holes. Going once around a ring of fence turns a full
+// circle: the outer ring has four more outward than inward turns, and each hole
+// four more inward than outward ones. The fence turns inwards at both corners of a
+// pinch, so areas that only touch the region at a corner count as separate holes.
+pub fn region_stats(garden: &[Vec<char>], labels: &Labels) -> Vec<Region> {
+    let mut regions: Vec<Region> = vec![];
+    let mut fences: Vec<PlotFences> = vec![PlotFences::default(); labels.n_regions];
+
+    for (i, row) in garden.iter().enumerate() {
+        for (j, &plant) in row.iter().enumerate() {
+            let label = labels.get(i, j);
+            if label == regions.len() {
+                regions.push(Region {
+                    plant,
+                    area: 0,
+                    perimeter: 0,
+                    sides: 0,
+                    min_i: i,
+                    min_j: j,
+                    max_i: i,
+                    max_j: j,
+                    holes: 0,
+                });
+            }
+
+            let region = &mut regions[label];
+            region.area += 1;
+            region.min_j = region.min_j.min(j);
+            region.max_i = region.max_i.max(i);
+            region.max_j = region.max_j.max(j);
+
+            fences[label].add(&plot_fences(i, j, |i, j| labels.at(i, j) == Some(label)));
+        }
+    }
+
+    for (region, fences) in regions.iter_mut().zip(&fences) {
+        region.perimeter = fences.perimeter;
+        region.sides = fences.sides();
+        // Turning outwards minus turning inwards is 4 * (1 - holes)
+        let outwards = fences.convex - fences.pinched;
+        let inwards = fences.concave + fences.pinched;
+        region.holes = (inwards + 4 - outwards) / 4;
+    }
+    regions
+}
+
+// Fences around a plot, given which plots are in its region
+#[derive(Debug, Default, Clone)]
+pub struct PlotFences {
+    pub perimeter: u64,
+    pub convex: u64,
+    pub concave: u64,
+    // Convex corners in a pinch
+    pub pinched: u64,
+}
+
+impl PlotFences {
+    pub fn add(&mut self, other: &PlotFences) {
+        self.perimeter += other.perimeter;
+        self.convex += other.convex;
+        self.concave += other.concave;
+        self.pinched += other.pinched;
+    }
+
+    pub fn sides(&self) -> u64 {
+        self.convex + self.concave
+    }
+}
+
+pub fn plot_fences(i: usize, j: usize, in_region: impl Fn(isize, isize) -> bool) -> PlotFences {
+    let (i, j) = (i as isize, j as isize);
+    let mut fences = PlotFences::default();
+    for (di, dj) in DIRECTIONS {
+        if !in_region(i + di, j + dj) {
+            fences.perimeter += 1;
+        }
+    }
+    for (di, dj) in DIAGONALS {
+        let along_i = in_region(i + di, j);
+        let along_j = in_region(i, j + dj);
+        if !along_i && !along_j {
+            fences.convex += 1;
+            if in_region(i + di, j + dj) {
+                fences.pinched += 1;
+            }
+        } else if along_i && along_j && !in_region(i + di, j + dj) {
+            fences.concave += 1;
+        }
+    }
+    fences
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> UnionFind {
+        UnionFind {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        // Path halving: every other node on the way now points to its grandparent
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
@@ -0,0 +1,192 @@
+use crate::solver::{checked, mul_add, Result, SolveError};
+use crate::Machine;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+// Lovász condition factor of the LLL reduction
+const DELTA: (i64, i64) = (3, 4);
+
+// One solution, and the kernel vectors
+pub type Lattice = (Vec<i128>, Vec<Vec<i128>>);
+
+// Integer solutions of moves · presses = prize, ignoring that presses can't be
+// negative: one solution and a basis of the kernel, or None if there are none.
+// Column operations that keep everything integer (swaps and adding multiples of a
+// column to another) turn the moves matrix into an echelon form, like Gaussian
+// elimination but with Euclid's algorithm on each row. The same operations on the
+// identity give the change of variables. The kernel basis that comes out of
+// this can have huge entries, so it is reduced before being returned.
+pub fn solve_lattice(machine: &Machine) -> Result<Option<Lattice>> {
+    let n = machine.buttons.len();
+    let dimensions = machine.prize.len();
+
+    // columns[k] is the k-th column of the moves matrix, transform[k] the one of
+    // the change of variables
+    let mut columns: Vec<Vec<i128>> = machine.buttons.iter().map(|b| b.moves.clone()).collect();
+    let mut transform: Vec<Vec<i128>> = (0..n)
+        .map(|k| (0..n).map(|j| i128::from(j == k)).collect())
+        .collect();
+    // Row each of the first `rank` columns has its leading entry in
+    let mut pivot_rows = vec![];
+
+    for row in 0..dimensions {
+        let rank = pivot_rows.len();
+        if rank == n {
+            break;
+        }
+        while let Some(smallest) = (rank..n)
+            .filter(|&k| columns[k][row] != 0)
+            .min_by_key(|&k| columns[k][row].abs())
+        {
+            columns.swap(rank, smallest);
+            transform.swap(rank, smallest);
+
+            let mut done = true;
+            for k in rank + 1..n {
+                let q = columns[k][row] / columns[rank][row];
+                subtract_multiple(&mut columns, k, q, rank)?;
+                subtract_multiple(&mut transform, k, q, rank)?;
+                done &= columns[k][row] == 0;
+            }
+            if done {
+                break;
+            }
+        }
+        if columns[rank][row] != 0 {
+            pivot_rows.push(row);
+        }
+    }
+    let rank = pivot_rows.len();
+
+    // Forward substitution, row by row
+    let mut y: Vec<i128> = vec![];
+    for (row, &target) in machine.prize.iter().enumerate() {
+        let known = y
+            .iter()
+            .enumerate()
+            .try_fold(0, |total, (k, &y_k)| mul_add(columns[k][row], y_k, total))?;
+        let remaining = checked(target.checked_sub(known))?;
+        if y.len() < rank && pivot_rows[y.len()] == row {
+            let pivot = columns[y.len()][row];
+            if remaining % pivot != 0 {
+                return Ok(None);
+            }
+            y.push(remaining / pivot);
+        } else if remaining != 0 {
+            return Ok(None);
+        }
+    }
+
+    let mut base = vec![0; n];
+    for (&y_k, column) in y.iter().zip(&transform) {
+        for (b, &t) in base.iter_mut().zip(column) {
+            *b = mul_add(y_k, t, *b)?;
+        }
+    }
+    let kernel = reduce_basis(transform.split_off(rank))?;
+    let base = size_reduce(base, &kernel)?;
+    Ok(Some((base, kernel)))
+}
+
+// vectors[target] -= q * vectors[source], with source < target
+fn subtract_multiple(
+    vectors: &mut [Vec<i128>],
+    target: usize,
+    q: i128,
+    source: usize,
+) -> Result<()> {
+    let (before, after) = vectors.split_at_mut(target);
+    for (t, &s) in after[0].iter_mut().zip(&before[source]) {
+        *t = mul_add(-q, s, *t)?;
+    }
+    Ok(())
+}
+
+fn to_big(v: &[i128]) -> Vec<BigInt> {
+    v.iter().map(|&x| BigInt::from(x)).collect()
+}
+
+fn to_i128(v: &[BigInt]) -> Result<Vec<i128>> {
+    v.iter()
+        .map(|x| x.to_i128().ok_or(SolveError::Overflow))
+        .collect()
+}
+
+fn dot(u: &[BigRational], v: &[BigRational]) -> BigRational {
+    u.iter().zip(v).map(|(a, b)| a * b).sum()
+}
+
+fn to_fractions(v: &[BigInt]) -> Vec<BigRational> {
+    v.iter()
+        .map(|x| BigRational::from_integer(x.clone()))
+        .collect()
+}
+
+// Gram-Schmidt orthogonalization of the vectors, without normalizing them
+fn gram_schmidt(vectors: &[Vec<BigInt>]) -> Vec<Vec<BigRational>> {
+    let mut orthogonal: Vec<Vec<BigRational>> = vec![];
+    for v in vectors {
+        let mut w = to_fractions(v);
+        for u in &orthogonal {
+            let mu = dot(&w, u) / dot(u, u);
+            for (w_i, u_i) in w.iter_mut().zip(u) {
+                *w_i -= &mu * u_i;
+            }
+        }
+        orthogonal.push(w);
+    }
+    orthogonal
+}
+
+// v -= round(v·u* / u*·u*) u, which makes v as short as it can be along u*
+fn reduce_against(v: &mut [BigInt], u: &[BigInt], u_star: &[BigRational]) {
+    let q = (dot(&to_fractions(v), u_star) / dot(u_star, u_star))
+        .round()
+        .to_integer();
+    if !q.is_zero() {
+        for (v_i, u_i) in v.iter_mut().zip(u) {
+            *v_i -= &q * u_i;
+        }
+    }
+}
+
+// LLL reduction: another basis of the same lattice, with short and nearly
+// orthogonal vectors. Kernels have a handful of vectors, so the Gram-Schmidt
+// basis is simply recomputed at each step.
+fn reduce_basis(basis: Vec<Vec<i128>>) -> Result<Vec<Vec<i128>>> {
+    let delta = BigRational::new(DELTA.0.into(), DELTA.1.into());
+    let mut basis: Vec<Vec<BigInt>> = basis.iter().map(|v| to_big(v)).collect();
+    let mut k = 1;
+    while k < basis.len() {
+        let orthogonal = gram_schmidt(&basis);
+        let (before, after) = basis.split_at_mut(k);
+        for j in (0..k).rev() {
+            reduce_against(&mut after[0], &before[j], &orthogonal[j]);
+        }
+
+        // Size reduction doesn't change the Gram-Schmidt vectors, only mu
+        let (previous, current) = (&orthogonal[k - 1], &orthogonal[k]);
+        let previous_norm = dot(previous, previous);
+        let mu = dot(&to_fractions(&basis[k]), previous) / &previous_norm;
+        if dot(current, current) >= (&delta - &mu * &mu) * previous_norm {
+            k += 1;
+        } else {
+            basis.swap(k - 1, k);
+            k = (k - 1).max(1);
+        }
+    }
+    basis.iter().map(|v| to_i128(v)).collect()
+}
+
+// Moves the solution as close to 0 as the kernel allows (Babai's nearest plane),
+// so that it has small entries too
+fn size_reduce(base: Vec<i128>, kernel: &[Vec<i128>]) -> Result<Vec<i128>> {
+    let kernel: Vec<Vec<BigInt>> = kernel.iter().map(|v| to_big(v)).collect();
+    let orthogonal = gram_schmidt(&kernel);
+    let mut base = to_big(&base);
+    for (v, v_star) in kernel.iter().zip(&orthogonal).rev() {
+        reduce_against(&mut base, v, v_star);
+    }
+    to_i128(&base)
+}
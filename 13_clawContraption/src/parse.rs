@@ -0,0 +1,293 @@
+use crate::{Button, Machine};
+use std::collections::HashMap;
+use std::fmt;
+
+// Costs of the puzzle's buttons, for buttons without a `cost=` annotation
+const DEFAULT_COSTS: [(&str, i128); 2] = [("A", 3), ("B", 1)];
+
+#[derive(Debug)]
+pub struct ParseError {
+    // Both counted from 1
+    pub machine: usize,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "machine {} (line {}): {}",
+            self.machine, self.line, self.message
+        )
+    }
+}
+
+// Machines are separated by blank lines. Each one has any number of button lines
+// and one prize line, in any order:
+//   Button A: X+94, Y-34
+//   Button C: X+2, Y+2, cost=5, max=10
+//   Prize: X=8400, Y=-5400, offset=1000
+// The prize names the axes. Buttons can list them in any order and leave some out
+// (they don't move along those). `cost=` is required for buttons other than A and
+// B, `max=` limits the presses (neither can be negative), and `offset=` is added
+// to every prize coordinate.
+pub fn parse_machines(input: &str) -> Result<Vec<Machine>, ParseError> {
+    let mut machines = vec![];
+    let mut block: Vec<(usize, &str)> = vec![];
+    for (l, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if !line.is_empty() {
+            block.push((l + 1, line));
+            continue;
+        }
+        if !block.is_empty() {
+            machines.push(parse_machine(machines.len() + 1, &block)?);
+            block.clear();
+        }
+    }
+    if !block.is_empty() {
+        machines.push(parse_machine(machines.len() + 1, &block)?);
+    }
+    Ok(machines)
+}
+
+fn parse_machine(machine: usize, lines: &[(usize, &str)]) -> Result<Machine, ParseError> {
+    let error = |line: usize, message: String| ParseError {
+        machine,
+        line,
+        message,
+    };
+
+    let mut buttons: Vec<(usize, &str, &str)> = vec![];
+    let mut prize: Option<(usize, &str)> = None;
+    for &(l, line) in lines {
+        let Some((kind, fields)) = line.split_once(':') else {
+            return Err(error(
+                l,
+                format!("expected `Button <label>:` or `Prize:`, got `{}`", line),
+            ));
+        };
+        match kind.split_whitespace().collect::<Vec<_>>()[..] {
+            ["Button", label] => {
+                if buttons.iter().any(|&(_, other, _)| other == label) {
+                    return Err(error(l, format!("button {} is defined twice", label)));
+                }
+                buttons.push((l, label, fields));
+            }
+            ["Prize"] => {
+                if prize.is_some() {
+                    return Err(error(l, "second prize line".to_string()));
+                }
+                prize = Some((l, fields));
+            }
+            _ => return Err(error(l, format!("unknown line `{}`", kind.trim()))),
+        }
+    }
+
+    let first_line = lines[0].0;
+    let Some((prize_line, prize_fields)) = prize else {
+        return Err(error(first_line, "missing prize line".to_string()));
+    };
+    if buttons.is_empty() {
+        return Err(error(first_line, "no buttons".to_string()));
+    }
+
+    let (axes, values, mut annotations) =
+        parse_fields(prize_fields, '=').map_err(|e| error(prize_line, e))?;
+    let offset = annotations.remove("offset").unwrap_or(0);
+    if let Some(name) = annotations.keys().next() {
+        return Err(error(
+            prize_line,
+            format!("unknown prize annotation `{}`", name),
+        ));
+    }
+    let prize = values
+        .iter()
+        .map(|v| v.checked_add(offset))
+        .collect::<Option<Vec<i128>>>()
+        .ok_or_else(|| error(prize_line, "offset overflows".to_string()))?;
+
+    let buttons = buttons
+        .into_iter()
+        .map(|(l, label, fields)| parse_button(label, fields, &axes).map_err(|e| error(l, e)))
+        .collect::<Result<_, _>>()?;
+    Ok(Machine { buttons, prize })
+}
+
+fn parse_button(label: &str, fields: &str, axes: &[String]) -> Result<Button, String> {
+    let (button_axes, values, mut annotations) = parse_fields(fields, '+')?;
+    let mut moves = vec![0; axes.len()];
+    for (axis, value) in button_axes.iter().zip(values) {
+        let Some(d) = axes.iter().position(|a| a == axis) else {
+            return Err(format!("axis {} isn't in the prize", axis));
+        };
+        moves[d] = value;
+    }
+
+    let cost = match annotations.remove("cost") {
+        Some(cost) => cost,
+        None => DEFAULT_COSTS
+            .iter()
+            .find(|&&(l, _)| l == label)
+            .map(|&(_, cost)| cost)
+            .ok_or_else(|| format!("button {} needs a cost, like `cost=2`", label))?,
+    };
+    if cost < 0 {
+        return Err(format!("button {} has a negative cost", label));
+    }
+    let max_presses = annotations.remove("max");
+    if max_presses.is_some_and(|max| max < 0) {
+        return Err(format!("button {} has a negative press limit", label));
+    }
+    if let Some(name) = annotations.keys().next() {
+        return Err(format!("unknown button annotation `{}`", name));
+    }
+
+    Ok(Button {
+        label: label.to_string(),
+        moves,
+        cost,
+        max_presses,
+    })
+}
+
+type Fields = (Vec<String>, Vec<i128>, HashMap<String, i128>);
+
+// Comma separated coordinates, like `X+3, Y-2` for buttons (`sign` is '+') or
+// `X=3, Y=-2` for prizes (`sign` is '='), and lowercase annotations like `cost=5`
+fn parse_fields(fields: &str, sign: char) -> Result<Fields, String> {
+    let mut axes: Vec<String> = vec![];
+    let mut values = vec![];
+    let mut annotations = HashMap::new();
+    for field in fields.split(',').map(str::trim) {
+        if field.is_empty() {
+            return Err("empty field".to_string());
+        }
+
+        let name_end = field
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(field.len());
+        let (name, rest) = field.split_at(name_end);
+        let rest = rest.trim_start();
+        if name.is_empty() {
+            return Err(format!("field `{}` has no name", field));
+        }
+
+        if name.starts_with(char::is_lowercase) {
+            let value = rest
+                .strip_prefix('=')
+                .ok_or_else(|| format!("expected `{}=<number>`", name))?;
+            let value = parse_number(value)?;
+            if annotations.insert(name.to_string(), value).is_some() {
+                return Err(format!("`{}` is given twice", name));
+            }
+            continue;
+        }
+
+        // Buttons move by +n or -n, prizes are at =n, =+n or =-n
+        let value = match (sign, rest.chars().next()) {
+            ('+', Some('+' | '-')) => parse_number(rest)?,
+            ('=', Some('=')) => parse_number(&rest[1..])?,
+            _ => {
+                let example = if sign == '+' { "+3` or `-3" } else { "=3" };
+                return Err(format!("expected `{}{}`, got `{}`", name, example, field));
+            }
+        };
+        if axes.iter().any(|a| a == name) {
+            return Err(format!("axis {} is given twice", name));
+        }
+        axes.push(name.to_string());
+        values.push(value);
+    }
+    if axes.is_empty() {
+        return Err("no coordinates".to_string());
+    }
+    Ok((axes, values, annotations))
+}
+
+fn parse_number(text: &str) -> Result<i128, String> {
+    let text = text.trim();
+    let digits = text.strip_prefix('+').unwrap_or(text);
+    digits
+        .parse()
+        .map_err(|_| format!("`{}` isn't a number", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_puzzle_input_with_crlf() {
+        let input = "Button A: X+94, Y+34\r\nButton B: X+22, Y+67\r\nPrize: X=8400, Y=5400\r\n\r\n\
+                     Button A: X+26, Y+66\r\nButton B: X+67, Y+21\r\nPrize: X=12748, Y=12176\r\n";
+        let machines = parse_machines(input).unwrap();
+        assert_eq!(machines.len(), 2);
+        assert_eq!(machines[0].buttons[0].moves, vec![94, 34]);
+        assert_eq!(machines[0].buttons[0].cost, 3);
+        assert_eq!(machines[0].buttons[1].cost, 1);
+        assert_eq!(machines[1].prize, vec![12748, 12176]);
+    }
+
+    #[test]
+    fn parses_extensions() {
+        let input = "Prize: X=10, Y=-4, Z=0, offset=100\n\
+                     Button C: Y-3, X+2, cost=5, max=7\n\
+                     Button A: X+1, Y+1, Z-1\n";
+        let machine = &parse_machines(input).unwrap()[0];
+        assert_eq!(machine.prize, vec![110, 96, 100]);
+        let c = &machine.buttons[0];
+        assert_eq!((c.label.as_str(), c.cost, c.max_presses), ("C", 5, Some(7)));
+        assert_eq!(c.moves, vec![2, -3, 0]);
+        assert_eq!(machine.buttons[1].moves, vec![1, 1, -1]);
+    }
+
+    fn error_at(input: &str) -> (usize, usize) {
+        let e = parse_machines(input).unwrap_err();
+        (e.machine, e.line)
+    }
+
+    #[test]
+    fn reports_machine_and_line() {
+        let first = "Button A: X+1, Y+1\nPrize: X=1, Y=1\n\n";
+        assert_eq!(
+            error_at(&format!("{}Button A: X+1\nButton B: X+1\n", first)),
+            (2, 4)
+        );
+        assert_eq!(
+            error_at(&format!("{}Button C: X+1\nPrize: X=1\n", first)),
+            (2, 4)
+        );
+        assert_eq!(
+            error_at(&format!("{}Button A: X*1\nPrize: X=1\n", first)),
+            (2, 4)
+        );
+        assert_eq!(
+            error_at(&format!("{}Button A: X+1\nPrize: X=1, Y=2, top=3\n", first)),
+            (2, 5)
+        );
+        assert_eq!(
+            error_at(&format!("{}Button A: W+1\nPrize: X=1\n", first)),
+            (2, 4)
+        );
+        assert_eq!(error_at(&format!("{}\n\nClaw: X=1\n", first)), (2, 6));
+    }
+
+    #[test]
+    fn rejects_negative_costs_and_limits() {
+        let error = |button: &str| {
+            let e = parse_machines(&format!("Prize: X=1\n{}\n", button)).unwrap_err();
+            (e.machine, e.line, e.message)
+        };
+        assert_eq!(
+            error("Button C: X+1, cost=-1"),
+            (1, 2, "button C has a negative cost".to_string())
+        );
+        assert_eq!(
+            error("Button A: X+1, max=-5"),
+            (1, 2, "button A has a negative press limit".to_string())
+        );
+        assert!(parse_machines("Prize: X=0\nButton C: X+1, cost=0, max=0\n").is_ok());
+    }
+}
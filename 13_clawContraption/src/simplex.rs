@@ -0,0 +1,168 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, Zero};
+
+pub enum LpResult {
+    Infeasible,
+    Unbounded,
+    Optimal(Vec<BigRational>),
+}
+
+// Minimizes c·z subject to a z <= b, with z free, using the two-phase simplex
+// method on a dense tableau of exact fractions. Each z is written as the
+// difference of two non-negative variables, and each constraint gets a slack
+// variable (plus an artificial one when b is negative, to find a first feasible
+// point). Bland's rule picks the pivots, so it never cycles.
+pub fn minimize(a: &[Vec<i128>], b: &[i128], c: &[i128]) -> LpResult {
+    let n = c.len();
+    let n_vars = 2 * n;
+    let first_artificial = n_vars + a.len();
+    let n_cols = first_artificial + b.iter().filter(|&&v| v < 0).count();
+    let fraction = |v: i128| BigRational::from_integer(BigInt::from(v));
+
+    let mut tableau = Tableau {
+        rows: vec![],
+        basis: vec![],
+        n_cols,
+    };
+    let mut artificial = first_artificial;
+    for (i, (a_row, &b_i)) in a.iter().zip(b).enumerate() {
+        let sign = if b_i < 0 { -1 } else { 1 };
+        let mut row = vec![BigRational::zero(); n_cols + 1];
+        for (j, &a_ij) in a_row.iter().enumerate() {
+            row[j] = fraction(sign * a_ij);
+            row[n + j] = fraction(-sign * a_ij);
+        }
+        row[n_vars + i] = fraction(sign);
+        row[n_cols] = fraction(sign * b_i);
+        if sign < 0 {
+            row[artificial] = BigRational::one();
+            tableau.basis.push(artificial);
+            artificial += 1;
+        } else {
+            tableau.basis.push(n_vars + i);
+        }
+        tableau.rows.push(row);
+    }
+
+    if n_cols > first_artificial {
+        let mut cost = vec![BigRational::zero(); n_cols];
+        cost[first_artificial..].fill(BigRational::one());
+        let infeasibility = tableau
+            .optimize(&cost, n_cols)
+            .expect("Finding a feasible point can't be unbounded");
+        if infeasibility.is_positive() {
+            return LpResult::Infeasible;
+        }
+        tableau.drive_out_artificials(first_artificial);
+    }
+
+    let mut cost = vec![BigRational::zero(); n_cols];
+    for (j, &c_j) in c.iter().enumerate() {
+        cost[j] = fraction(c_j);
+        cost[n + j] = fraction(-c_j);
+    }
+    if tableau.optimize(&cost, first_artificial).is_none() {
+        return LpResult::Unbounded;
+    }
+
+    let mut w = vec![BigRational::zero(); n_vars];
+    for (row, &basic) in tableau.rows.iter().zip(&tableau.basis) {
+        if basic < n_vars {
+            w[basic] = row[n_cols].clone();
+        }
+    }
+    LpResult::Optimal((0..n).map(|j| &w[j] - &w[n + j]).collect())
+}
+
+struct Tableau {
+    // Constraints, with the right-hand side in the last column
+    rows: Vec<Vec<BigRational>>,
+    // Basic variable of each row
+    basis: Vec<usize>,
+    n_cols: usize,
+}
+
+impl Tableau {
+    // Minimizes cost·w from the current basis, only letting the first `allowed`
+    // columns into it. Returns the minimum, or None if it's unbounded.
+    fn optimize(&mut self, cost: &[BigRational], allowed: usize) -> Option<BigRational> {
+        // Reduced costs, with minus the current value in the last column
+        let mut objective = cost.to_vec();
+        objective.push(BigRational::zero());
+        for (row, &basic) in self.rows.iter().zip(&self.basis) {
+            let factor = objective[basic].clone();
+            if !factor.is_zero() {
+                for (o, v) in objective.iter_mut().zip(row) {
+                    *o -= &factor * v;
+                }
+            }
+        }
+
+        loop {
+            let Some(entering) = (0..allowed).find(|&j| objective[j].is_negative()) else {
+                return Some(-&objective[self.n_cols]);
+            };
+
+            let mut leaving: Option<(usize, BigRational)> = None;
+            for (i, row) in self.rows.iter().enumerate() {
+                if !row[entering].is_positive() {
+                    continue;
+                }
+                let ratio = &row[self.n_cols] / &row[entering];
+                let better = leaving.as_ref().is_none_or(|(l, best)| {
+                    ratio < *best || (ratio == *best && self.basis[i] < self.basis[*l])
+                });
+                if better {
+                    leaving = Some((i, ratio));
+                }
+            }
+
+            let (leaving, _) = leaving?;
+            self.pivot(leaving, entering, &mut objective);
+        }
+    }
+
+    fn pivot(&mut self, r: usize, c: usize, objective: &mut [BigRational]) {
+        let pivot = self.rows[r][c].clone();
+        for v in self.rows[r].iter_mut() {
+            *v /= &pivot;
+        }
+        let pivot_row = self.rows[r].clone();
+        let eliminate = |row: &mut [BigRational]| {
+            let factor = row[c].clone();
+            if !factor.is_zero() {
+                for (v, p) in row.iter_mut().zip(&pivot_row) {
+                    *v -= &factor * p;
+                }
+            }
+        };
+        for (i, row) in self.rows.iter_mut().enumerate() {
+            if i != r {
+                eliminate(row);
+            }
+        }
+        eliminate(objective);
+        self.basis[r] = c;
+    }
+
+    // After the first phase, artificial variables still in the basis are all 0.
+    // They are swapped for real ones, or their row is dropped if it's redundant.
+    fn drive_out_artificials(&mut self, first_artificial: usize) {
+        let mut unused_objective = vec![BigRational::zero(); self.n_cols + 1];
+        let mut i = 0;
+        while i < self.rows.len() {
+            if self.basis[i] >= first_artificial {
+                match (0..first_artificial).find(|&j| !self.rows[i][j].is_zero()) {
+                    Some(j) => self.pivot(i, j, &mut unused_objective),
+                    None => {
+                        self.rows.remove(i);
+                        self.basis.remove(i);
+                        continue;
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use crate::lattice::solve_lattice;
+use crate::simplex::{minimize, LpResult};
+use crate::Machine;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+// Branch and bound nodes to explore before giving up
+const MAX_NODES: usize = 100_000;
+
+// Number of times each button is pressed
+pub type Presses = Vec<i128>;
+
+#[derive(Debug)]
+pub enum SolveError {
+    // Some intermediate value doesn't fit in an i128
+    Overflow,
+    // A button moves the claw in a different number of dimensions than the prize
+    Dimensions,
+    // Pressing some buttons more and more keeps making it cheaper
+    Unbounded,
+    // The branch and bound search went on for too long
+    SearchLimit,
+    // The presses found don't take the claw to the prize, which is a bug
+    WrongSolution(Presses),
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Overflow => write!(f, "arithmetic overflow"),
+            SolveError::Dimensions => write!(f, "buttons and prize have different dimensions"),
+            SolveError::Unbounded => write!(f, "the cost has no minimum"),
+            SolveError::SearchLimit => {
+                write!(f, "gave up after exploring {} branches", MAX_NODES)
+            }
+            SolveError::WrongSolution(p) => write!(f, "pressing {:?} misses the prize", p),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, SolveError>;
+
+pub fn checked(value: Option<i128>) -> Result<i128> {
+    value.ok_or(SolveError::Overflow)
+}
+
+// a * b + c
+pub fn mul_add(a: i128, b: i128, c: i128) -> Result<i128> {
+    checked(a.checked_mul(b).and_then(|ab| ab.checked_add(c)))
+}
+
+pub fn cost(machine: &Machine, presses: &[i128]) -> Result<i128> {
+    machine
+        .buttons
+        .iter()
+        .zip(presses)
+        .try_fold(0, |total, (button, &n)| mul_add(button.cost, n, total))
+}
+
+// Cheapest presses that take the claw to the prize, or None if it can't be reached
+pub fn solve(machine: &Machine) -> Result<Option<Presses>> {
+    let dimensions = machine.prize.len();
+    if machine.buttons.iter().any(|b| b.moves.len() != dimensions) {
+        return Err(SolveError::Dimensions);
+    }
+
+    let presses = match solve_cramer(machine)? {
+        Some(presses) => presses,
+        None => solve_lattice_problem(machine)?,
+    };
+    if let Some(p) = &presses {
+        check_solution(machine, p)?;
+    }
+    Ok(presses)
+}
+
+// The ways to reach the prize, without limits on presses, are a lattice: one
+// solution plus any integer combination of some kernel vectors (which don't move
+// the claw). The search is over the coefficients z of that combination, where
+// the cost is linear and the press limits are linear constraints.
+fn solve_lattice_problem(machine: &Machine) -> Result<Option<Presses>> {
+    let Some(problem) = Problem::new(machine)? else {
+        return Ok(None);
+    };
+    let z = match problem.kernel.len() {
+        0 => problem.is_feasible(&[])?.then(Vec::new),
+        1 => problem.solve_line()?,
+        _ => problem.branch_and_bound()?,
+    };
+    let Some(z) = z else {
+        return Ok(None);
+    };
+
+    Ok(Some(problem.presses(&z)?))
+}
+
+// Two buttons moving the claw in different directions on a 2D grid, like in the
+// puzzle: there is exactly one way (maybe fractional or negative) to reach the
+// prize. None if the machine isn't like that; collinear buttons have a line of
+// solutions, which the general case handles.
+fn solve_cramer(machine: &Machine) -> Result<Option<Option<Presses>>> {
+    let [a, b] = &machine.buttons[..] else {
+        return Ok(None);
+    };
+    let &[ci, cj] = &machine.prize[..] else {
+        return Ok(None);
+    };
+    let (ai, aj, bi, bj) = (a.moves[0], a.moves[1], b.moves[0], b.moves[1]);
+
+    let determinant = cross(ai, aj, bi, bj)?;
+    if determinant == 0 {
+        return Ok(None);
+    }
+    let a_numerator = cross(ci, cj, bi, bj)?;
+    let b_numerator = cross(ai, aj, ci, cj)?;
+    if a_numerator % determinant != 0 || b_numerator % determinant != 0 {
+        return Ok(Some(None));
+    }
+
+    let presses = vec![a_numerator / determinant, b_numerator / determinant];
+    let allowed = machine
+        .buttons
+        .iter()
+        .zip(&presses)
+        .all(|(button, &n)| n >= 0 && button.max_presses.is_none_or(|max| n <= max));
+    Ok(Some(allowed.then_some(presses)))
+}
+
+// a * d - b * c
+fn cross(a: i128, b: i128, c: i128, d: i128) -> Result<i128> {
+    checked(
+        a.checked_mul(d)
+            .zip(b.checked_mul(c))
+            .and_then(|(ad, bc)| ad.checked_sub(bc)),
+    )
+}
+
+fn check_solution(machine: &Machine, presses: &[i128]) -> Result<()> {
+    let wrong = || Err(SolveError::WrongSolution(presses.to_vec()));
+    for (d, &target) in machine.prize.iter().enumerate() {
+        let position = machine
+            .buttons
+            .iter()
+            .zip(presses)
+            .try_fold(0, |total, (button, &n)| mul_add(button.moves[d], n, total))?;
+        if position != target {
+            return wrong();
+        }
+    }
+    for (button, &n) in machine.buttons.iter().zip(presses) {
+        if n < 0 || button.max_presses.is_some_and(|max| n > max) {
+            return wrong();
+        }
+    }
+    Ok(())
+}
+
+fn to_i128(value: BigInt) -> Result<i128> {
+    value.to_i128().ok_or(SolveError::Overflow)
+}
+
+// coefs·z <= bound
+#[derive(Clone)]
+struct Constraint {
+    coefs: Vec<i128>,
+    bound: i128,
+}
+
+// Extra constraints of a branch and bound node, and the solution of its linear
+// program. None once the node is expanded.
+type Node = Option<(Vec<Constraint>, Vec<BigRational>)>;
+
+struct Problem {
+    // Presses are base + z[0] * kernel[0] + z[1] * kernel[1] + ...
+    base: Vec<i128>,
+    kernel: Vec<Vec<i128>>,
+    // Presses can't be negative or go over the limits
+    constraints: Vec<Constraint>,
+    // Cost is the cost of base plus cost_slope·z
+    cost_slope: Vec<i128>,
+}
+
+impl Problem {
+    // None if no combination of presses (even negative ones) reaches the prize
+    fn new(machine: &Machine) -> Result<Option<Problem>> {
+        let Some((base, kernel)) = solve_lattice(machine)? else {
+            return Ok(None);
+        };
+
+        let mut constraints = vec![];
+        for (k, button) in machine.buttons.iter().enumerate() {
+            let coefs: Vec<i128> = kernel.iter().map(|v| v[k]).collect();
+            constraints.push(Constraint {
+                coefs: coefs.iter().map(|c| -c).collect(),
+                bound: base[k],
+            });
+            if let Some(max) = button.max_presses {
+                constraints.push(Constraint {
+                    coefs,
+                    bound: checked(max.checked_sub(base[k]))?,
+                });
+            }
+        }
+
+        let cost_slope = kernel
+            .iter()
+            .map(|v| cost(machine, v))
+            .collect::<Result<_>>()?;
+        Ok(Some(Problem {
+            cost_slope,
+            base,
+            kernel,
+            constraints,
+        }))
+    }
+
+    fn presses(&self, z: &[i128]) -> Result<Presses> {
+        let mut presses = self.base.clone();
+        for (v, &z_k) in self.kernel.iter().zip(z) {
+            for (p, &v_k) in presses.iter_mut().zip(v) {
+                *p = mul_add(z_k, v_k, *p)?;
+            }
+        }
+        Ok(presses)
+    }
+
+    fn is_feasible(&self, z: &[i128]) -> Result<bool> {
+        for constraint in &self.constraints {
+            let value = z
+                .iter()
+                .zip(&constraint.coefs)
+                .try_fold(0, |total, (&z_k, &c)| mul_add(z_k, c, total))?;
+            if value > constraint.bound {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // With a single kernel vector the constraints give a range of z, and the
+    // cheapest z is at one of its ends
+    fn solve_line(&self) -> Result<Option<Vec<i128>>> {
+        let mut z_min: Option<i128> = None;
+        let mut z_max: Option<i128> = None;
+        for constraint in &self.constraints {
+            let (c, bound) = (constraint.coefs[0], constraint.bound);
+            match c.signum() {
+                1 => {
+                    let max = bound.div_euclid(c);
+                    z_max = Some(z_max.map_or(max, |z| z.min(max)));
+                }
+                -1 => {
+                    // c * z <= bound, so z >= bound / c rounded up
+                    let min = -bound.div_euclid(-c);
+                    z_min = z_min.max(Some(min));
+                }
+                _ if bound < 0 => return Ok(None),
+                _ => {}
+            }
+        }
+        if let (Some(min), Some(max)) = (z_min, z_max) {
+            if min > max {
+                return Ok(None);
+            }
+        }
+
+        let z = match self.cost_slope[0].signum() {
+            1 => z_min.ok_or(SolveError::Unbounded)?,
+            -1 => z_max.ok_or(SolveError::Unbounded)?,
+            _ => z_min.or(z_max).unwrap_or(0),
+        };
+        Ok(Some(vec![z]))
+    }
+
+    // Best-first branch and bound. Each node solves the problem without requiring
+    // z to be integers (a linear program, in exact fractions), which gives a lower
+    // bound on the cost. The node with the lowest bound is expanded next, so the
+    // first one whose z are all integers is the cheapest. Otherwise the node splits
+    // on a fractional z[k], into z[k] <= floor and z[k] >= floor + 1.
+    fn branch_and_bound(&self) -> Result<Option<Vec<i128>>> {
+        let n = self.kernel.len();
+        let mut nodes: Vec<Node> = vec![];
+        // Lower bound and index of the nodes to expand. Among equal bounds, the
+        // newest nodes (deeper in the tree) come first.
+        let mut queue: BinaryHeap<(Reverse<i128>, usize)> = BinaryHeap::new();
+        let push = |branches: Vec<Constraint>, nodes: &mut Vec<Node>, queue: &mut BinaryHeap<_>| {
+            if let Some((lower_bound, z)) = self.relax(&branches)? {
+                queue.push((Reverse(lower_bound), nodes.len()));
+                nodes.push(Some((branches, z)));
+            }
+            Ok(())
+        };
+        push(vec![], &mut nodes, &mut queue)?;
+
+        while let Some((_, i)) = queue.pop() {
+            if nodes.len() > MAX_NODES {
+                return Err(SolveError::SearchLimit);
+            }
+            let (branches, z) = nodes[i].take().expect("Nodes are expanded once");
+            let Some(k) = z.iter().position(|z| !z.is_integer()) else {
+                let z = z.iter().map(|z| to_i128(z.to_integer()));
+                return Ok(Some(z.collect::<Result<_>>()?));
+            };
+
+            let floor = to_i128(z[k].floor().to_integer())?;
+            let mut unit = vec![0; n];
+            unit[k] = 1;
+            let mut up = branches.clone();
+            up.push(Constraint {
+                coefs: unit.iter().map(|u| -u).collect(),
+                bound: checked((-floor).checked_sub(1))?,
+            });
+            let mut down = branches;
+            down.push(Constraint {
+                coefs: unit,
+                bound: floor,
+            });
+            push(up, &mut nodes, &mut queue)?;
+            push(down, &mut nodes, &mut queue)?;
+        }
+        Ok(None)
+    }
+
+    // Solution of the linear program with the extra constraints, and the lowest
+    // cost_slope·z an integer solution under it can have. None if it has no solution.
+    fn relax(&self, branches: &[Constraint]) -> Result<Option<(i128, Vec<BigRational>)>> {
+        let constraints: Vec<&Constraint> = self.constraints.iter().chain(branches).collect();
+        let a: Vec<Vec<i128>> = constraints.iter().map(|c| c.coefs.clone()).collect();
+        let b: Vec<i128> = constraints.iter().map(|c| c.bound).collect();
+        let z = match minimize(&a, &b, &self.cost_slope) {
+            LpResult::Infeasible => return Ok(None),
+            LpResult::Unbounded => return Err(SolveError::Unbounded),
+            LpResult::Optimal(z) => z,
+        };
+
+        // Costs are integers, so the lowest one is the relaxed cost rounded up
+        let relaxed_cost: BigRational = z
+            .iter()
+            .zip(&self.cost_slope)
+            .map(|(z, &s)| z * BigRational::from_integer(s.into()))
+            .sum();
+        let lower_bound = to_i128(relaxed_cost.ceil().to_integer())?;
+        Ok(Some((lower_bound, z)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Button;
+    use proptest::prelude::*;
+
+    fn machine(buttons: Vec<(Vec<i128>, i128)>, max: Option<i128>, prize: Vec<i128>) -> Machine {
+        let buttons = buttons
+            .into_iter()
+            .enumerate()
+            .map(|(k, (moves, cost))| Button {
+                label: ((b'A' + k as u8) as char).to_string(),
+                moves,
+                cost,
+                max_presses: max,
+            })
+            .collect();
+        Machine { buttons, prize }
+    }
+
+    fn position(machine: &Machine, presses: &[i128]) -> Vec<i128> {
+        (0..machine.prize.len())
+            .map(|d| {
+                machine
+                    .buttons
+                    .iter()
+                    .zip(presses)
+                    .map(|(b, &n)| b.moves[d] * n)
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn solved_cost(machine: &Machine) -> Option<i128> {
+        solve(machine)
+            .unwrap()
+            .map(|presses| cost(machine, &presses).unwrap())
+    }
+
+    // Cheapest cost over every way of pressing the buttons up to `max` times each
+    fn brute_force(machine: &Machine, max: i128) -> Option<i128> {
+        let n = machine.buttons.len();
+        let mut presses = vec![0; n];
+        let mut best: Option<i128> = None;
+        loop {
+            if position(machine, &presses) == machine.prize {
+                let cost = cost(machine, &presses).unwrap();
+                best = Some(best.map_or(cost, |b| b.min(cost)));
+            }
+
+            let Some(k) = (0..n).find(|&k| presses[k] < max) else {
+                return best;
+            };
+            presses[k] += 1;
+            presses[..k].fill(0);
+        }
+    }
+
+    // Cheapest cost on a 2D machine, enumerating the presses of all the buttons
+    // but the last two, which are then solved for directly
+    fn enumerate_all_but_two(machine: &Machine, max: i128) -> Option<i128> {
+        let n = machine.buttons.len();
+        let (a, b) = (&machine.buttons[n - 2], &machine.buttons[n - 1]);
+        let mut presses = vec![0; n];
+        let mut best: Option<i128> = None;
+        loop {
+            let reached = position(machine, &presses);
+            let (ci, cj) = (machine.prize[0] - reached[0], machine.prize[1] - reached[1]);
+            let determinant = a.moves[0] * b.moves[1] - a.moves[1] * b.moves[0];
+            let last_two: Vec<(i128, i128)> = if determinant != 0 {
+                let x = ci * b.moves[1] - cj * b.moves[0];
+                let y = a.moves[0] * cj - a.moves[1] * ci;
+                if x % determinant == 0 && y % determinant == 0 {
+                    vec![(x / determinant, y / determinant)]
+                } else {
+                    vec![]
+                }
+            } else {
+                (0..=max)
+                    .filter_map(|x| {
+                        let (ri, rj) = (ci - a.moves[0] * x, cj - a.moves[1] * x);
+                        let d = if b.moves[0] != 0 { 0 } else { 1 };
+                        let y = [ri, rj][d] / b.moves[d];
+                        (b.moves[0] * y == ri && b.moves[1] * y == rj).then_some((x, y))
+                    })
+                    .collect()
+            };
+            for (x, y) in last_two {
+                if (0..=max).contains(&x) && (0..=max).contains(&y) {
+                    presses[n - 2] = x;
+                    presses[n - 1] = y;
+                    let cost = cost(machine, &presses).unwrap();
+                    best = Some(best.map_or(cost, |b| b.min(cost)));
+                }
+            }
+            presses[n - 2] = 0;
+            presses[n - 1] = 0;
+
+            let Some(k) = (0..n - 2).find(|&k| presses[k] < max) else {
+                return best;
+            };
+            presses[k] += 1;
+            presses[..k].fill(0);
+        }
+    }
+
+    // Buttons with moves and costs in the given ranges, and a prize that half of
+    // the time is reached by pressing each button up to `max` times
+    fn machines(
+        n_buttons: std::ops::RangeInclusive<usize>,
+        dimensions: std::ops::RangeInclusive<usize>,
+        moves: std::ops::Range<i128>,
+        max: i128,
+    ) -> impl Strategy<Value = Machine> {
+        (n_buttons, dimensions).prop_flat_map(move |(n, d)| {
+            let buttons =
+                prop::collection::vec((prop::collection::vec(moves.clone(), d), 1i128..6), n);
+            let presses = prop::collection::vec(0..=max, n);
+            let prize = prop::collection::vec(-5i128..30, d);
+            (buttons, presses, prize, any::<bool>()).prop_map(
+                move |(buttons, presses, prize, reachable)| {
+                    let mut m = machine(buttons, Some(max), prize);
+                    if reachable {
+                        m.prize = position(&m, &presses);
+                    }
+                    m
+                },
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn small_machines_match_brute_force(m in machines(2..=4, 2..=3, -3..6, 6)) {
+            prop_assert_eq!(solved_cost(&m), brute_force(&m, 6), "{:?}", m);
+        }
+
+        #[test]
+        fn puzzle_sized_machines_match_enumeration(m in machines(3..=4, 2..=2, 10..100, 100)) {
+            prop_assert_eq!(solved_cost(&m), enumerate_all_but_two(&m, 100), "{:?}", m);
+        }
+
+        #[test]
+        fn cramer_matches_lattice(m in machines(2..=2, 2..=2, -20..100, 100)) {
+            if let Some(presses) = solve_cramer(&m).unwrap() {
+                let lattice = solve_lattice_problem(&m).unwrap();
+                prop_assert_eq!(
+                    presses.map(|p| cost(&m, &p).unwrap()),
+                    lattice.map(|p| cost(&m, &p).unwrap()),
+                    "{:?}", m
+                );
+            }
+        }
+
+        // Without limits on presses and with prizes as far as in part 2. The presses
+        // used to make up the prize are a solution, so there has to be one at
+        // least as cheap.
+        #[test]
+        fn far_prizes_are_solved(
+            buttons in prop::collection::vec((prop::collection::vec(10i128..100, 2), 1i128..6), 3..=4),
+            presses in prop::collection::vec(0i128..1_000_000_000_000, 4),
+        ) {
+            let mut m = machine(buttons, None, vec![0, 0]);
+            let presses = &presses[..m.buttons.len()];
+            m.prize = position(&m, presses);
+            let solved = solved_cost(&m).expect("The prize can be reached");
+            prop_assert!(solved <= cost(&m, presses).unwrap(), "{:?}", m);
+        }
+    }
+
+    #[test]
+    fn four_buttons_with_big_kernel_entries() {
+        let m = machine(
+            vec![
+                (vec![12, 58], 3),
+                (vec![88, 85], 1),
+                (vec![27, 17], 6),
+                (vec![90, 52], 6),
+            ],
+            Some(100),
+            vec![12639, 10782],
+        );
+        let presses = solve(&m).unwrap().unwrap();
+        assert_eq!(cost(&m, &presses).unwrap(), 624);
+        assert_eq!(enumerate_all_but_two(&m, 100), Some(624));
+    }
+}
@@ -1,3 +1,4 @@
+use num_bigint::BigUint;
 use regex::Regex;
 use std::collections::HashSet;
 use std::env;
@@ -12,22 +13,81 @@ struct RobotState {
     dy: i32,
 }
 
+const DEFAULT_SECONDS: i32 = 100;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let input_file = args.get(1).expect("Input file is required");
     let input = parse_input(input_file);
 
+    let get_arg = |name: &str| {
+        args.iter().position(|a| a == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {}", name))
+        })
+    };
+    let parse_arg = |name: &str| {
+        get_arg(name).map(|value| {
+            value
+                .parse::<i32>()
+                .unwrap_or_else(|_| panic!("Invalid value for {}: {}", name, value))
+        })
+    };
+
+    let (width, height) = match (parse_arg("--width"), parse_arg("--height")) {
+        (Some(width), Some(height)) => (width, height),
+        (width, height) => {
+            let (inferred_width, inferred_height) = infer_size(&input);
+            (
+                width.unwrap_or(inferred_width),
+                height.unwrap_or(inferred_height),
+            )
+        }
+    };
+    assert!(width > 0 && height > 0, "The grid can't be empty");
+    for robot in &input {
+        assert!(
+            (0..width).contains(&robot.x) && (0..height).contains(&robot.y),
+            "Robot {:?} starts outside the {}x{} grid",
+            robot,
+            width,
+            height
+        );
+    }
+
+    let seconds = parse_arg("--seconds").unwrap_or(DEFAULT_SECONDS);
+    if args.contains(&"--occupancy".to_string()) {
+        print_occupancy(&input, seconds, width, height);
+        return;
+    }
+
+    let partitions = get_arg("--partitions").map_or((2, 2), |value| {
+        let parse = |n: &str| n.parse::<i32>().ok().filter(|&n| n > 0);
+        value
+            .split_once('x')
+            .and_then(|(columns, rows)| Some((parse(columns)?, parse(rows)?)))
+            .unwrap_or_else(|| panic!("Invalid value for --partitions: {}", value))
+    });
+
     let now = Instant::now();
     let result = if args.contains(&"--part-2".to_string()) {
-        part_2(input)
+        BigUint::from(part_2(input, width, height) as u64)
     } else {
-        part_1(input)
+        part_1(input, seconds, width, height, partitions)
     };
     let elapsed = now.elapsed();
-    println!("{:?}", result);
+    println!("{}", result);
     println!("Executed in {:?}", elapsed);
 }
 
+// Smallest grid holding every robot, when the size isn't given
+fn infer_size(input: &[RobotState]) -> (i32, i32) {
+    let width = input.iter().map(|r| r.x + 1).max().unwrap_or(1);
+    let height = input.iter().map(|r| r.y + 1).max().unwrap_or(1);
+    eprintln!("Grid size not given, using {}x{}", width, height);
+    (width, height)
+}
+
 fn parse_input(input_file: &str) -> Vec<RobotState> {
     let line_pattern = Regex::new(r"p=(\d+),(\d+) v=(-?\d+),(-?\d+)").unwrap();
     fs::read_to_string(input_file)
@@ -45,49 +105,50 @@ fn parse_input(input_file: &str) -> Vec<RobotState> {
         .collect()
 }
 
-fn part_1(input: Vec<RobotState>) -> i32 {
-    let mut q1 = 0;
-    let mut q2 = 0;
-    let mut q3 = 0;
-    let mut q4 = 0;
-
-    // const WIDTH: i32 = 11;
-    // const HEIGHT: i32 = 7;
-    const WIDTH: i32 = 101;
-    const HEIGHT: i32 = 103;
-
+fn part_1(
+    input: Vec<RobotState>,
+    seconds: i32,
+    width: i32,
+    height: i32,
+    partitions: (i32, i32),
+) -> BigUint {
+    let (n_columns, n_rows) = partitions;
+    let mut counts = vec![0u64; (n_columns * n_rows) as usize];
     input.into_iter().for_each(|robot| {
-        let final_state = do_n_iterations(&robot, 100, WIDTH, HEIGHT);
-        let (x, y) = (final_state.x, final_state.y);
-        if y < HEIGHT / 2 {
-            if x < WIDTH / 2 {
-                q1 += 1;
-            } else if x > WIDTH / 2 {
-                q2 += 1;
-            }
-        } else if y > HEIGHT / 2 {
-            if x < WIDTH / 2 {
-                q3 += 1;
-            } else if x > WIDTH / 2 {
-                q4 += 1;
-            }
+        let final_state = do_n_iterations(&robot, seconds, width, height);
+        if let (Some(column), Some(row)) = (
+            partition(final_state.x, width, n_columns),
+            partition(final_state.y, height, n_rows),
+        ) {
+            counts[(row * n_columns + column) as usize] += 1;
         }
     });
-    q1 * q2 * q3 * q4
+    // Many partitions can take the product past any fixed-size integer
+    counts.iter().map(|&n| BigUint::from(n)).product()
+}
+
+// Which of `n_parts` equal parts of `size` a cell is in, or None if it's on the
+// line between two of them. Lines go through the middle of a cell when `size`
+// doesn't split evenly, like the middle row and column for quadrants of an odd
+// grid. Cell `x` spans [x, x + 1), so it's cut when its centre x + 1/2 is at some
+// t * size / n_parts.
+fn partition(x: i32, size: i32, n_parts: i32) -> Option<i32> {
+    let (centre, parts) = ((2 * x + 1) as i64 * n_parts as i64, 2 * size as i64);
+    (centre % parts != 0).then_some((centre / parts) as i32)
 }
 
-fn part_2(input: Vec<RobotState>) -> i32 {
-    const WIDTH: i32 = 101;
-    const HEIGHT: i32 = 103;
+fn part_2(input: Vec<RobotState>, width: i32, height: i32) -> i32 {
     let mut n_iter = 0;
     loop {
+        // Every robot is back where it started after width * height seconds
+        assert!(n_iter < width * height, "Robots never stop overlapping");
         let mut robot_positions = HashSet::new();
         input.iter().for_each(|robot| {
-            let final_state = do_n_iterations(robot, n_iter, WIDTH, HEIGHT);
+            let final_state = do_n_iterations(robot, n_iter, width, height);
             robot_positions.insert((final_state.x, final_state.y));
         });
         if robot_positions.len() == input.len() {
-            print_map(&robot_positions, WIDTH, HEIGHT);
+            print_map(&robot_positions, width, height);
             break;
         }
         n_iter += 1;
@@ -96,9 +157,14 @@ fn part_2(input: Vec<RobotState>) -> i32 {
 }
 
 fn do_n_iterations(state: &RobotState, n: i32, width: i32, height: i32) -> RobotState {
+    // Robots are back where they started every `width` (or `height`) seconds, so
+    // this doesn't overflow however big `n` is
+    let position = |p: i32, dp: i32, size: i32| {
+        (p as i64 + dp as i64 * (n % size) as i64).rem_euclid(size as i64) as i32
+    };
     RobotState {
-        x: ((state.x + (state.dx * n)) % width + width) % width,
-        y: ((state.y + (state.dy * n)) % height + height) % height,
+        x: position(state.x, state.dx, width),
+        y: position(state.y, state.dy, height),
         dx: state.dx,
         dy: state.dy,
     }
@@ -107,8 +173,98 @@ fn do_n_iterations(state: &RobotState, n: i32, width: i32, height: i32) -> Robot
 fn print_map(positions: &HashSet<(i32, i32)>, width: i32, height: i32) {
     for y in 0..height {
         for x in 0..width {
-            print!("{}", if positions.contains(&(x, y)) { '#' } else { '.' });
+            print!(
+                "{}",
+                if positions.contains(&(x, y)) {
+                    '#'
+                } else {
+                    '.'
+                }
+            );
         }
         println!();
     }
 }
+
+// Number of robots on each cell after `seconds`, as in the puzzle's examples
+fn print_occupancy(input: &[RobotState], seconds: i32, width: i32, height: i32) {
+    let mut counts = vec![vec![0; width as usize]; height as usize];
+    for robot in input {
+        let final_state = do_n_iterations(robot, seconds, width, height);
+        counts[final_state.y as usize][final_state.x as usize] += 1;
+    }
+    // Counts are separated when some of them take more than one digit
+    let separator = if counts.iter().flatten().any(|&n| n > 9) {
+        " "
+    } else {
+        ""
+    };
+    for row in counts {
+        let line: String = row
+            .iter()
+            .map(|&n| match n {
+                0 => ".".to_string(),
+                n => n.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(separator);
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn many_seconds_dont_overflow() {
+        let robot = RobotState {
+            x: 2,
+            y: 4,
+            dx: 2,
+            dy: -3,
+        };
+        let after = |n| {
+            let state = do_n_iterations(&robot, n, 11, 7);
+            (state.x, state.y)
+        };
+        // 11 * 7 * 27889 seconds is a whole number of cycles
+        assert_eq!(after(11 * 7 * 27_889), (2, 4));
+        assert_eq!(after(i32::MAX), after(i32::MAX % 77));
+    }
+
+    #[test]
+    fn safety_factor_of_many_partitions_doesnt_overflow() {
+        // 20 robots on each cell of a 4x4 grid, which doesn't move them
+        let robots: Vec<RobotState> = (0..16)
+            .flat_map(|cell| {
+                (0..20).map(move |_| RobotState {
+                    x: cell % 4,
+                    y: cell / 4,
+                    dx: 0,
+                    dy: 0,
+                })
+            })
+            .collect();
+        // 20^16 is more than u64::MAX
+        let expected = BigUint::from(20u32).pow(16);
+        assert!(expected > BigUint::from(u64::MAX));
+        assert_eq!(part_1(robots, 100, 4, 4, (4, 4)), expected);
+    }
+
+    #[test]
+    fn partitions_skip_cut_cells() {
+        // Quadrants of an odd side leave out the middle
+        let halves: Vec<_> = (0..7).map(|x| partition(x, 7, 2)).collect();
+        assert_eq!(
+            halves,
+            [Some(0), Some(0), Some(0), None, Some(1), Some(1), Some(1)]
+        );
+        // Lines between cells don't leave anything out
+        let thirds: Vec<_> = (0..9).map(|x| partition(x, 9, 3)).collect();
+        assert_eq!(thirds, [0, 0, 0, 1, 1, 1, 2, 2, 2].map(Some));
+        // Quarters of 6 cut cells 1 and 4, at 1.5 and 4.5
+        let quarters: Vec<_> = (0..6).map(|x| partition(x, 6, 4)).collect();
+        assert_eq!(quarters, [Some(0), None, Some(1), Some(2), None, Some(3)]);
+    }
+}